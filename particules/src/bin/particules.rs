@@ -0,0 +1,352 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::LevelFilter;
+
+use particules::export::{ExportFormat, Exporter};
+use particules::resource::ResourceRules;
+use particules::scenario::Scenario;
+use particules::sma::Sma;
+use particules::stop::StopCondition;
+use particules::sweep::SweepSpec;
+use particules::validation::Validation;
+
+const MODELS: [&str; 2] = ["particules", "wator"];
+const STOP_CONDITIONS: [&str; 5] = ["extinction", "full", "empty", "steady", "cycle"];
+
+fn main() {
+    let matches = App::new("particules")
+        .about("Runs particules and Wa-Tor simulations without any GUI")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(run_command())
+        .subcommand(sweep_command())
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("run", Some(args)) => {
+            init_logger(args.occurrences_of("verbose"));
+            run(args)
+        }
+        ("sweep", Some(args)) => {
+            init_logger(args.occurrences_of("verbose"));
+            sweep(args)
+        }
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run_command<'a, 'b>() -> App<'a, 'b> {
+    let option =
+        |name: &'a str, help: &'a str| Arg::with_name(name).long(name).takes_value(true).help(help);
+
+    SubCommand::with_name("run")
+        .about("Run a simulation and write per-tick statistics")
+        .arg(option(
+            "scenario",
+            "Scenario file (json, toml or ron), completed by the options below",
+        ))
+        .arg(option("model", "Simulation to run [default: wator]").possible_values(&MODELS))
+        .arg(option("width", "Grid width [default: 100]"))
+        .arg(option("height", "Grid height [default: 100]"))
+        .arg(option(
+            "density",
+            "Percentage of cells holding a particule [default: 10]",
+        ))
+        .arg(option(
+            "fish-density",
+            "Number of fish placed at start [default: 200]",
+        ))
+        .arg(option(
+            "shark-density",
+            "Number of sharks placed at start [default: 20]",
+        ))
+        .arg(option(
+            "fish-breed-time",
+            "Ticks between two fish births [default: 3]",
+        ))
+        .arg(option(
+            "shark-breed-time",
+            "Ticks between two shark births [default: 10]",
+        ))
+        .arg(option(
+            "shark-starve-time",
+            "Ticks a shark can go without eating [default: 3]",
+        ))
+        .arg(option(
+            "rules",
+            "Shark rules, classic or energy [default: classic]",
+        ))
+        .arg(option(
+            "initial-energy",
+            "Energy of the sharks placed at start [default: 6]",
+        ))
+        .arg(option(
+            "fish-energy",
+            "Energy gained per fish eaten [default: 4]",
+        ))
+        .arg(option(
+            "tick-cost",
+            "Energy lost by sharks on each tick [default: 1]",
+        ))
+        .arg(option(
+            "move-cost",
+            "Energy lost by sharks on each move [default: 0]",
+        ))
+        .arg(option(
+            "breed-threshold",
+            "Energy from which sharks breed [default: 12]",
+        ))
+        .arg(option(
+            "regrowth",
+            "Resource layer regrowth, delay:<ticks> or logistic:<rate>",
+        ))
+        .arg(option(
+            "grass-energy",
+            "Energy gained by fish per unit of resource [default: 0]",
+        ))
+        .arg(
+            Arg::with_name("random-age")
+                .long("random-age")
+                .help("Place fish and sharks at a random point of their breeding cycle"),
+        )
+        .arg(option(
+            "topology",
+            "bounded, torus, horizontal_cylinder or vertical_cylinder",
+        ))
+        .arg(option(
+            "neighbourhood",
+            "moore, von_neumann or moore:<radius>",
+        ))
+        .arg(option(
+            "scheduler",
+            "shuffled, sequential, synchronous or random_async",
+        ))
+        .arg(option(
+            "conflict-policy",
+            "random, first_come, oldest, hungriest or stall",
+        ))
+        .arg(option(
+            "seed",
+            "Seed of the random number generator, random if omitted",
+        ))
+        .arg(option("ticks", "Maximum number of ticks to run").default_value("1000"))
+        .arg(
+            option("stop", "Stop as soon as the condition holds")
+                .possible_values(&STOP_CONDITIONS)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(option("steady-ticks", "Ticks without any move for --stop steady").default_value("10"))
+        .arg(option("cycles", "Population oscillations for --stop cycle").default_value("3"))
+        .arg(option(
+            "output",
+            "File to write statistics to, stdout if omitted",
+        ))
+        .arg(option("format", "Statistics file format, csv or jsonl").default_value("csv"))
+        .arg(verbose_arg())
+}
+
+fn sweep_command<'a, 'b>() -> App<'a, 'b> {
+    let option =
+        |name: &'a str, help: &'a str| Arg::with_name(name).long(name).takes_value(true).help(help);
+
+    SubCommand::with_name("sweep")
+        .about("Run Wa-Tor over a grid of parameters and write one summary row per run")
+        .arg(option("spec", "JSON file listing the values of every parameter").required(true))
+        .arg(option(
+            "output",
+            "CSV file to write summaries to, stdout if omitted",
+        ))
+        .arg(option(
+            "threads",
+            "Number of runs in parallel, one per core if omitted",
+        ))
+        .arg(verbose_arg())
+}
+
+fn verbose_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("verbose")
+        .short("v")
+        .long("verbose")
+        .multiple(true)
+        .help("Log simulation events on stderr, repeat for more details")
+}
+
+fn init_logger(verbosity: u64) {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new().filter_level(level).init();
+}
+
+fn run(args: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    let mut sma = build(args)?;
+    let mut conditions = vec![StopCondition::MaxTurns(parse(args, "ticks")?)];
+    for condition in args.values_of("stop").into_iter().flatten() {
+        conditions.push(match condition {
+            "extinction" => StopCondition::Extinction,
+            "full" => StopCondition::GridFull,
+            "steady" => StopCondition::SteadyState(parse(args, "steady-ticks")?),
+            "cycle" => StopCondition::PopulationCycle(parse(args, "cycles")?),
+            "empty" => StopCondition::custom("no agent left", |sma| sma.agents.is_empty()),
+            other => unreachable!("unknown stop condition {}", other),
+        });
+    }
+
+    let format: ExportFormat = parse(args, "format")?;
+    sma.attach_exporter(match args.value_of("output") {
+        Some(path) => Exporter::create(path, format)?,
+        None => Exporter::new(BufWriter::new(io::stdout()), format),
+    });
+
+    let outcome = sma.run_until(&conditions);
+    match sma.detach_exporter() {
+        Some(mut exporter) => exporter.flush()?,
+        None => return Err("statistics export failed, see the log above".into()),
+    }
+
+    eprintln!(
+        "stopped after {} ticks (seed {}): {}",
+        sma.turn, sma.seed, outcome
+    );
+    Ok(())
+}
+
+fn sweep(args: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    if args.value_of("threads").is_some() {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(parse(args, "threads")?)
+            .build_global()?;
+    }
+    let path = args.value_of("spec").unwrap_or_default();
+    let spec: SweepSpec = serde_json::from_reader(BufReader::new(File::open(path)?))
+        .map_err(|err| format!("invalid sweep specification {}: {}", path, err))?;
+
+    let results = spec.run()?;
+
+    let writer: Box<dyn Write> = match args.value_of("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut failures = 0;
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            Ok(summary) => writer.serialize(summary)?,
+            Err(err) => {
+                failures += 1;
+                eprintln!("run {} failed: {}", i, err);
+            }
+        }
+    }
+    writer.flush()?;
+
+    eprintln!("{} runs completed", results.len() - failures);
+    if failures > 0 {
+        return Err(format!("{} of {} runs failed", failures, results.len()).into());
+    }
+    Ok(())
+}
+
+/// Scenario file, or default scenario, updated with the options given on the command line.
+fn build(args: &ArgMatches) -> Result<Sma, Box<dyn error::Error>> {
+    let mut scenario = match args.value_of("scenario") {
+        Some(path) => Scenario::read(path)?,
+        None => Scenario::default(),
+    };
+
+    // Unparsable options are reported together with the problems of the resulting scenario.
+    let mut validation = Validation::new();
+    let v = &mut validation;
+    override_with(v, args, "model", &mut scenario.model);
+    override_with(v, args, "width", &mut scenario.width);
+    override_with(v, args, "height", &mut scenario.height);
+    override_with(v, args, "density", &mut scenario.density);
+    override_with(v, args, "fish-density", &mut scenario.fish_density);
+    override_with(v, args, "shark-density", &mut scenario.shark_density);
+    override_with(v, args, "fish-breed-time", &mut scenario.fish_breed_time);
+    override_with(v, args, "shark-breed-time", &mut scenario.shark_breed_time);
+    override_with(
+        v,
+        args,
+        "shark-starve-time",
+        &mut scenario.shark_starve_time,
+    );
+    override_with(v, args, "rules", &mut scenario.rules);
+    override_with(
+        v,
+        args,
+        "initial-energy",
+        &mut scenario.energy.initial_energy,
+    );
+    override_with(v, args, "fish-energy", &mut scenario.energy.fish_energy);
+    override_with(v, args, "tick-cost", &mut scenario.energy.tick_cost);
+    override_with(v, args, "move-cost", &mut scenario.energy.move_cost);
+    override_with(
+        v,
+        args,
+        "breed-threshold",
+        &mut scenario.energy.breed_threshold,
+    );
+    override_with(v, args, "grass-energy", &mut scenario.energy.grass_energy);
+    if args.value_of("regrowth").is_some() {
+        let resources = scenario
+            .resources
+            .get_or_insert_with(ResourceRules::default);
+        override_with(v, args, "regrowth", &mut resources.regrowth);
+    }
+    if args.is_present("random-age") {
+        scenario.random_age = true;
+    }
+    override_with(v, args, "topology", &mut scenario.topology);
+    override_with(v, args, "neighbourhood", &mut scenario.neighbourhood);
+    override_with(v, args, "scheduler", &mut scenario.scheduler);
+    override_with(v, args, "conflict-policy", &mut scenario.conflict_policy);
+    if args.value_of("seed").is_some() {
+        let mut seed = 0;
+        override_with(v, args, "seed", &mut seed);
+        scenario.seed = Some(seed);
+    }
+
+    scenario.check(&mut validation);
+    validation.finish()?;
+    Ok(scenario.build()?)
+}
+
+fn override_with<T>(validation: &mut Validation, args: &ArgMatches, name: &str, field: &mut T)
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(value) = args.value_of(name) {
+        match value.parse() {
+            Ok(value) => *field = value,
+            Err(err) => {
+                let message = format!("invalid value '{}': {}", value, err);
+                validation.report(format!("--{}", name), message);
+            }
+        }
+    }
+}
+
+fn parse<T>(args: &ArgMatches, name: &str) -> Result<T, Box<dyn error::Error>>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    let value = args.value_of(name).unwrap_or_default();
+    value
+        .parse()
+        .map_err(|err| format!("invalid value '{}' for --{}: {}", value, name, err).into())
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How to settle several agents deciding to move into the same cell during a synchronous tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// A random contender moves, the others stall.
+    #[default]
+    Random,
+    /// The contender that decided first moves, the others stall.
+    FirstCome,
+    /// The contender created first moves.
+    Oldest,
+    /// The contender closest to starvation moves, ties go to the first one.
+    Hungriest,
+    /// Nobody moves into a contested cell.
+    Stall,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 5] = [
+        ConflictPolicy::Random,
+        ConflictPolicy::FirstCome,
+        ConflictPolicy::Oldest,
+        ConflictPolicy::Hungriest,
+        ConflictPolicy::Stall,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ConflictPolicy::Random => "random",
+            ConflictPolicy::FirstCome => "first_come",
+            ConflictPolicy::Oldest => "oldest",
+            ConflictPolicy::Hungriest => "hungriest",
+            ConflictPolicy::Stall => "stall",
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        ConflictPolicy::ALL
+            .iter()
+            .find(|policy| policy.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownConflictPolicy(value.into()))
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{AgentCommand};
+use crate::snapshot::AgentSnapshot;
+use crate::{AgentCommand, SmaRng};
 
 use super::Direction;
 use super::environment::Cell;
@@ -7,51 +8,86 @@ use super::HDirection;
 use super::Point;
 use super::VDirection;
 
+/// What every agent can do, optional capabilities are exposed through the `as_*` accessors.
 pub trait AgentBehavior {
-    fn decide(&mut self, environment: &Environment);
+    fn decide(&mut self, environment: &Environment, rng: &mut SmaRng);
     fn update(&mut self, environment: &mut Environment) -> AgentCommand;
-    fn collision(&self) -> bool;
-    fn direction(&self) -> Direction;
+
+    /// Update under `Scheduler::Synchronous`: every agent decided on the same environment, so
+    /// none may alter another, the decision of the other agent would be based on stale state.
+    fn update_synchronous(&mut self, environment: &mut Environment) -> AgentCommand {
+        self.update(environment)
+    }
     fn coordinate(&self) -> Point;
     fn set_coordinate(&mut self, point: Point);
-    fn previous_coordinate(&self) -> Point;
-    fn set_previous_coordinate(&mut self, point: Point);
-    fn set_collision(&mut self, collision: bool);
-    fn set_direction(&mut self, direction: Direction);
     fn get_color(&self) -> (f32, f32, f32);
 
-    fn peek(&self, environment: &Environment, x_offset: i32, y_offset: i32) -> Option<Point> {
-        let position = Point::new(
-            self.coordinate().x + x_offset,
-            self.coordinate().y + y_offset,
-        );
-        if let Some(Cell::Empty) = environment.get_cell(position) {
-            Some(position)
-        } else {
-            None
-        }
+    /// Cell the pending decision will move into, if any.
+    fn target(&self, environment: &Environment) -> Option<Point>;
+
+    /// Cell other than its own the pending decision may alter, the target by default.
+    fn reach(&self, environment: &Environment) -> Option<Point> {
+        self.target(environment)
+    }
+
+    /// Drop the pending decision, the agent stays where it is this tick.
+    fn stall(&mut self);
+
+    /// What the last update ended up doing.
+    fn last_action(&self) -> Action;
+
+    /// Copy of the agent state, used to save the simulation.
+    fn snapshot(&self) -> AgentSnapshot;
+
+    fn as_directed(&self) -> Option<&dyn Directed> {
+        None
+    }
+
+    fn as_directed_mut(&mut self) -> Option<&mut dyn Directed> {
+        None
+    }
+
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        None
+    }
+
+    fn as_collidable_mut(&mut self) -> Option<&mut dyn Collidable> {
+        None
     }
 
-    fn peek_cell<'a>(
-        &self,
-        environment: &'a Environment,
-        x_offset: i32,
-        y_offset: i32,
-    ) -> Option<(&'a Cell, Point)> {
-        let position = Point::new(
-            self.coordinate().x + x_offset,
-            self.coordinate().y + y_offset,
-        );
-        environment.get_cell(position).map(|cell| (cell, position))
+    fn as_energy(&self) -> Option<&dyn HasEnergy> {
+        None
     }
 
+    fn as_breeds(&self) -> Option<&dyn Breeds> {
+        None
+    }
+}
+
+/// Outcome of an update, as counted in the tick statistics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Moved,
+    Stalled,
+    /// Bumped into another agent.
+    Collided,
+    /// Bounced off a wall.
+    Bounced,
+}
+
+/// Agents moving along a direction, one cell per tick.
+pub trait Directed: AgentBehavior {
+    fn direction(&self) -> Direction;
+    fn set_direction(&mut self, direction: Direction);
+    fn previous_coordinate(&self) -> Point;
+    fn set_previous_coordinate(&mut self, point: Point);
+
     fn move_forward(&mut self, environment: &mut Environment) {
         self.set_previous_coordinate(self.coordinate());
 
-        let forward_position = if environment.borderless {
-            self.look_ahead_borderless(environment.width, environment.height)
-        } else {
-            self.look_ahead()
+        let forward_position = match environment.normalize(self.look_ahead()) {
+            Some(position) => position,
+            None => return,
         };
 
         match environment.get_cell(forward_position) {
@@ -82,43 +118,22 @@ pub trait AgentBehavior {
             },
         }
     }
+}
 
-    fn look_ahead_borderless(&self, width: i32, height: i32) -> Point {
-        Point {
-            x: match self.direction().x {
-                HDirection::Right => {
-                    if self.coordinate().x + 1 > width - 1 {
-                        0
-                    } else {
-                        self.coordinate().x + 1
-                    }
-                }
-                HDirection::Left => {
-                    if self.coordinate().x - 1 < 0 {
-                        width - 1
-                    } else {
-                        self.coordinate().x - 1
-                    }
-                }
-                _ => self.coordinate().x,
-            },
-            y: match self.direction().y {
-                VDirection::Up => {
-                    if self.coordinate().y - 1 < 0 {
-                        height - 1
-                    } else {
-                        self.coordinate().y - 1
-                    }
-                }
-                VDirection::Down => {
-                    if self.coordinate().y + 1 > height - 1 {
-                        0
-                    } else {
-                        self.coordinate().y + 1
-                    }
-                }
-                _ => self.coordinate().y,
-            },
-        }
-    }
+/// Agents that can bump into each other.
+pub trait Collidable: AgentBehavior {
+    fn collision(&self) -> bool;
+    fn set_collision(&mut self, collision: bool);
+}
+
+/// Agents that die when they run out of energy.
+pub trait HasEnergy: AgentBehavior {
+    /// Energy left under `EnergyRules`, ticks left before starving under the classic rules.
+    fn energy(&self) -> u32;
+}
+
+/// Agents giving birth on a regular basis.
+pub trait Breeds: AgentBehavior {
+    /// Ticks left before the next birth.
+    fn breed_count_down(&self) -> u32;
 }
@@ -1,8 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use super::AgentId;
 use super::AgentKind;
 use super::AgentRef;
+use super::Error;
 use super::Point;
+use crate::neighbourhood::Neighbourhood;
+use crate::resource::ResourceLayer;
+use crate::topology::Topology;
+use crate::wator::energy::EnergyRules;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Cell {
     Empty,
     Filled(AgentRef),
@@ -29,38 +37,57 @@ impl Cell {
         }
     }
 
-    pub(crate) fn into_agent(&self) -> &AgentRef {
+    pub(crate) fn into_agent(&self) -> Option<&AgentRef> {
         match self {
-            Cell::Filled(a) => a,
-            _ => panic!("Expected an agent"),
+            Cell::Filled(a) => Some(a),
+            _ => None,
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Environment {
     pub(crate) width: i32,
     pub(crate) height: i32,
+    /// Rebuilt from the agents when a snapshot is loaded.
+    #[serde(skip)]
     pub(crate) cells: Vec<Cell>,
-    pub(crate) borderless: bool,
+    pub(crate) topology: Topology,
+    pub(crate) neighbourhood: Neighbourhood,
     pub(crate) fish_breed_time: u8,
     pub(crate) shark_breed_time: u8,
     pub(crate) shark_starve_time: u8,
+    /// Energy budget of the sharks when set, the classic count-downs otherwise.
+    #[serde(default)]
+    pub(crate) energy_rules: Option<EnergyRules>,
+    #[serde(default)]
+    pub(crate) resources: Option<ResourceLayer>,
+    next_agent_id: u64,
 }
 
 impl Environment {
-    pub fn debug(&self) {
-        self.cells.iter().enumerate().for_each(|(i, cell)| {
-            if let Cell::Filled(a) = cell {
-                println!("cell {}:{}", a.coordinate().x, a.coordinate().y);
-                let agent_idx = self.get_index(a.coordinate());
-                println!("cell idx = {}, agent coord to idx = {}", i, agent_idx);
-            } else {
-                println!("empty cell");
+    /// One line per row, `.` for empty cells and the first letter of the agent kind otherwise.
+    /// Cells whose agent believes it stands elsewhere are shown as `!`.
+    pub fn debug(&self) -> String {
+        let mut dump = String::with_capacity(self.cells.len() + self.height as usize);
+        for (idx, cell) in self.cells.iter().enumerate() {
+            dump.push(match cell {
+                Cell::Empty => '.',
+                Cell::Filled(agent) if self.get_index(agent.coordinate()) != idx => '!',
+                Cell::Filled(agent) => match agent.kind() {
+                    AgentKind::Fish => 'f',
+                    AgentKind::Shark => 's',
+                    AgentKind::Particule => 'p',
+                },
+            });
+            if (idx + 1) % self.width as usize == 0 {
+                dump.push('\n');
             }
-        })
+        }
+        dump
     }
     pub fn is_out_of_bound_y(&self, y: i32) -> bool {
-        if self.borderless {
+        if self.topology.wraps_y() {
             false
         } else if y > self.height - 1 || y < 0 {
             true
@@ -70,7 +97,7 @@ impl Environment {
     }
 
     pub fn is_out_of_bound_x(&self, x: i32) -> bool {
-        if self.borderless {
+        if self.topology.wraps_x() {
             false
         } else if x > self.width - 1 || x < 0 {
             true
@@ -79,6 +106,61 @@ impl Environment {
         }
     }
 
+    /// Cells around `point`, wrapped across connected edges, each cell listed once.
+    pub fn neighbours(&self, point: Point, neighbourhood: &Neighbourhood) -> Vec<(&Cell, Point)> {
+        let mut neighbours: Vec<(&Cell, Point)> = vec![];
+        for (x, y) in neighbourhood.offsets() {
+            let position = match self.normalize(Point::new(point.x + x, point.y + y)) {
+                Some(position) if position != point => position,
+                _ => continue,
+            };
+
+            if neighbours.iter().all(|(_, known)| *known != position) {
+                let idx = self.get_index(position);
+                neighbours.push((&self.cells[idx], position));
+            }
+        }
+        neighbours
+    }
+
+    /// Bring a point back inside the environment, `None` if it falls off a wall.
+    pub fn normalize(&self, point: Point) -> Option<Point> {
+        if self.out_of_bound(point) {
+            None
+        } else {
+            Some(self.wrap(point))
+        }
+    }
+
+    fn wrap(&self, point: Point) -> Point {
+        Point::new(
+            if self.topology.wraps_x() {
+                point.x.rem_euclid(self.width)
+            } else {
+                point.x
+            },
+            if self.topology.wraps_y() {
+                point.y.rem_euclid(self.height)
+            } else {
+                point.y
+            },
+        )
+    }
+
+    /// Reserve the identifier of an agent about to be created in this environment.
+    pub fn new_agent_id(&mut self) -> AgentId {
+        let id = AgentId(self.next_agent_id);
+        self.next_agent_id += 1;
+        id
+    }
+
+    pub fn is_empty(&self, point: Point) -> bool {
+        match self.get_cell(point) {
+            Some(cell) => cell.is_empty_cell(),
+            None => false,
+        }
+    }
+
     pub fn swap(&mut self, a: Point, b: Point) {
         let a = self.get_index(a);
         let b = self.get_index(b);
@@ -92,9 +174,32 @@ impl Environment {
     }
 
     pub fn get_index(&self, point: Point) -> usize {
+        let point = self.wrap(point);
         (point.y * self.width + point.x) as usize
     }
 
+    pub fn has_resources(&self) -> bool {
+        self.resources.is_some()
+    }
+
+    /// Share of the resource capacity left on the cell, `None` without a resource layer.
+    pub fn resource(&self, point: Point) -> Option<f32> {
+        if self.out_of_bound(point) {
+            return None;
+        }
+        let index = self.get_index(point);
+        self.resources.as_ref().map(|layer| layer.fraction(index))
+    }
+
+    /// Eat from the resources of the cell, returns the quantity eaten.
+    pub(crate) fn graze(&mut self, point: Point) -> f32 {
+        let index = self.get_index(point);
+        match &mut self.resources {
+            Some(layer) => layer.consume(index),
+            None => 0.0,
+        }
+    }
+
     pub fn get_cell(&self, point: Point) -> Option<&Cell> {
         if self.out_of_bound(point) {
             None
@@ -113,9 +218,9 @@ impl Environment {
         }
     }
 
-    pub fn set_cell(&mut self, point: Point, cell: Cell) -> Result<(), &str> {
+    pub fn set_cell(&mut self, point: Point, cell: Cell) -> Result<(), Error> {
         if self.out_of_bound(point) {
-            Err("Cannot set cell out of bounds!")
+            Err(Error::OutOfBounds(point))
         } else {
             let idx = self.get_index(point);
             self.cells.push(cell);
@@ -124,17 +229,37 @@ impl Environment {
         }
     }
 
-    pub fn set_agent_cell(&mut self, from: Point, to: Point) {
+    pub fn set_agent_cell(&mut self, from: Point, to: Point) -> Result<(), Error> {
+        if self.out_of_bound(from) {
+            return Err(Error::OutOfBounds(from));
+        }
+        if self.out_of_bound(to) {
+            return Err(Error::OutOfBounds(to));
+        }
+
         let current = self.get_index(from);
-        let agent = self.cells[current].into_agent().clone();
+        let agent = match self.cells[current].into_agent() {
+            Some(agent) => agent.clone(),
+            None => return Err(Error::EmptyCell(from)),
+        };
         let idx = self.get_index(to);
 
         self.cells[current] = Cell::Empty;
-        agent.set_coordinate(to);
-        self.cells[idx] = Cell::Filled(agent.clone());
+        agent.set_coordinate(self.wrap(to));
+        self.cells[idx] = Cell::Filled(agent);
+        Ok(())
+    }
+
+    pub fn check_dimensions(width: i32, height: i32) -> Result<(), Error> {
+        if width > 0 && height > 0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidDimensions { width, height })
+        }
     }
 
-    pub fn new(width: i32, height: i32, borderless: bool) -> Self {
+    pub fn new(width: i32, height: i32, topology: Topology) -> Result<Self, Error> {
+        Environment::check_dimensions(width, height)?;
         let mut cells = vec![];
         let size = width * height;
 
@@ -142,25 +267,30 @@ impl Environment {
             cells.push(Cell::Empty);
         }
 
-        Environment {
+        Ok(Environment {
             width,
             height,
             cells,
-            borderless,
+            topology,
+            neighbourhood: Neighbourhood::default(),
             fish_breed_time: 0,
             shark_breed_time: 0,
             shark_starve_time: 0,
-        }
+            energy_rules: None,
+            resources: None,
+            next_agent_id: 0,
+        })
     }
 
     pub fn new_fish_shark(
         width: i32,
         height: i32,
-        borderless: bool,
+        topology: Topology,
         fish_breed_time: u8,
         shark_breed_time: u8,
         shark_starve_time: u8,
-    ) -> Self {
+    ) -> Result<Self, Error> {
+        Environment::check_dimensions(width, height)?;
         let mut cells = vec![];
         let size = width * height;
 
@@ -168,15 +298,19 @@ impl Environment {
             cells.push(Cell::Empty);
         }
 
-        Environment {
+        Ok(Environment {
             width,
             height,
             cells,
-            borderless,
+            topology,
+            neighbourhood: Neighbourhood::default(),
             shark_breed_time,
             fish_breed_time,
             shark_starve_time,
-        }
+            energy_rules: None,
+            resources: None,
+            next_agent_id: 0,
+        })
     }
 }
 
@@ -184,14 +318,18 @@ impl Environment {
 mod test {
     use crate::environment::Cell;
     use crate::environment::Environment;
+    use crate::neighbourhood::Neighbourhood;
+    use crate::topology::Topology;
     use crate::wator::fish::Decision;
     use crate::wator::fish::Fish;
+    use crate::wator::lifecycle::Lifecycle;
+    use crate::AgentId;
     use crate::AgentRef;
     use crate::Point;
 
     #[test]
     fn should_get_index() {
-        let env = Environment::new(5, 5, false);
+        let env = Environment::new(5, 5, Topology::Bounded).unwrap();
 
         assert_eq!(env.get_index(Point::new(0, 0)), 0);
         assert_eq!(env.get_index(Point::new(1, 0)), 1);
@@ -205,16 +343,19 @@ mod test {
 
     #[test]
     fn should_set_cell() {
-        let mut env = Environment::new(5, 5, false);
+        let mut env = Environment::new(5, 5, Topology::Bounded).unwrap();
 
         let agent = Fish {
             coordinate: Point::new(0, 0),
             decision: Decision::Stall,
-            breed_count_down: 0,
+            lifecycle: Lifecycle::fish(0),
         };
 
-        env.set_cell(Point::new(0, 0), Cell::Filled(AgentRef::from_fish(agent)))
-            .unwrap();
+        env.set_cell(
+            Point::new(0, 0),
+            Cell::Filled(AgentRef::from_fish(agent, AgentId(0))),
+        )
+        .unwrap();
 
         let res = env.get_cell(Point::new(0, 0)).unwrap();
         match res {
@@ -228,17 +369,19 @@ mod test {
 
     #[test]
     fn should_swap_cells() {
-        let mut env = Environment::new(5, 5, false);
+        let mut env = Environment::new(5, 5, Topology::Bounded).unwrap();
 
         let agent = Fish {
             coordinate: Point::new(0, 0),
             decision: Decision::Stall,
-            breed_count_down: 0,
+            lifecycle: Lifecycle::fish(0),
         };
 
-        let agent = &AgentRef::from_fish(agent);
-        env.set_agent_cell(Point::new(0, 0), agent.coordinate());
-        env.set_agent_cell(Point::new(1, 0), agent.coordinate());
+        let agent = AgentRef::from_fish(agent, AgentId(0));
+        env.set_cell(agent.coordinate(), Cell::Filled(agent.clone()))
+            .unwrap();
+        env.set_agent_cell(agent.coordinate(), Point::new(1, 0))
+            .unwrap();
 
         let expected_filled = env.get_cell(Point::new(1, 0)).unwrap();
         let expected_empty = env.get_cell(Point::new(0, 0)).unwrap();
@@ -258,4 +401,139 @@ mod test {
             _ => panic!("expected empty"),
         };
     }
+
+    fn neighbour_points(
+        env: &Environment,
+        point: Point,
+        neighbourhood: Neighbourhood,
+    ) -> Vec<Point> {
+        env.neighbours(point, &neighbourhood)
+            .into_iter()
+            .map(|(_, point)| point)
+            .collect()
+    }
+
+    #[test]
+    fn should_list_neighbours() {
+        let env = Environment::new(5, 5, Topology::Bounded).unwrap();
+        let center = Point::new(2, 2);
+
+        assert_eq!(
+            neighbour_points(&env, center, Neighbourhood::Moore).len(),
+            8
+        );
+        assert_eq!(
+            neighbour_points(&env, center, Neighbourhood::ExtendedMoore(2)).len(),
+            24
+        );
+        assert_eq!(
+            neighbour_points(&env, center, Neighbourhood::VonNeumann),
+            vec![
+                Point::new(2, 1),
+                Point::new(1, 2),
+                Point::new(3, 2),
+                Point::new(2, 3)
+            ]
+        );
+        assert_eq!(
+            neighbour_points(&env, center, Neighbourhood::Custom(vec![(0, 0), (2, -2)])),
+            vec![Point::new(4, 0)]
+        );
+    }
+
+    #[test]
+    fn should_wrap_neighbours_on_torus() {
+        let bounded = Environment::new(5, 5, Topology::Bounded).unwrap();
+        let torus = Environment::new(5, 5, Topology::Torus).unwrap();
+        let corner = Point::new(0, 0);
+
+        assert_eq!(
+            neighbour_points(&bounded, corner, Neighbourhood::Moore).len(),
+            3
+        );
+
+        let wrapped = neighbour_points(&torus, corner, Neighbourhood::Moore);
+        assert_eq!(wrapped.len(), 8);
+        assert!(wrapped.contains(&Point::new(4, 4)));
+        assert!(wrapped.contains(&Point::new(4, 0)));
+        assert!(wrapped.contains(&Point::new(0, 4)));
+
+        // A radius larger than the world must not list a cell twice, nor the center itself
+        let small = Environment::new(3, 3, Topology::Torus).unwrap();
+        let points = neighbour_points(&small, Point::new(1, 1), Neighbourhood::ExtendedMoore(2));
+        assert_eq!(points.len(), 8);
+        assert!(!points.contains(&Point::new(1, 1)));
+    }
+
+    #[test]
+    fn should_normalize_points_for_each_topology() {
+        let outside = [
+            Point::new(-1, 2),
+            Point::new(5, 2),
+            Point::new(2, -1),
+            Point::new(2, 5),
+        ];
+        let expected = |topology| match topology {
+            Topology::Bounded => vec![None, None, None, None],
+            Topology::Torus => vec![
+                Some(Point::new(4, 2)),
+                Some(Point::new(0, 2)),
+                Some(Point::new(2, 4)),
+                Some(Point::new(2, 0)),
+            ],
+            Topology::HorizontalCylinder => {
+                vec![Some(Point::new(4, 2)), Some(Point::new(0, 2)), None, None]
+            }
+            Topology::VerticalCylinder => {
+                vec![None, None, Some(Point::new(2, 4)), Some(Point::new(2, 0))]
+            }
+        };
+
+        for topology in Topology::ALL.iter().cloned() {
+            let env = Environment::new(5, 5, topology).unwrap();
+            let normalized: Vec<Option<Point>> =
+                outside.iter().map(|point| env.normalize(*point)).collect();
+
+            assert_eq!(normalized, expected(topology), "{}", topology);
+            assert_eq!(env.normalize(Point::new(3, 3)), Some(Point::new(3, 3)));
+        }
+    }
+
+    #[test]
+    fn should_store_wrapped_coordinates() {
+        let mut env = Environment::new(5, 5, Topology::Torus).unwrap();
+        let agent = Fish {
+            coordinate: Point::new(4, 4),
+            decision: Decision::Stall,
+            lifecycle: Lifecycle::fish(0),
+        };
+        let agent_ref = AgentRef::from_fish(agent, AgentId(0));
+        env.set_cell(Point::new(4, 4), Cell::Filled(agent_ref.clone()))
+            .unwrap();
+
+        env.set_agent_cell(Point::new(4, 4), Point::new(5, 5))
+            .unwrap();
+
+        assert_eq!(agent_ref.coordinate(), Point::new(0, 0));
+        assert_eq!(env.get_index(Point::new(5, 5)), 0);
+        assert!(env.get_cell(Point::new(0, 0)).unwrap().is_fish());
+        assert!(env.is_empty(Point::new(4, 4)));
+    }
+
+    #[test]
+    fn should_dump_environment() {
+        let mut env = Environment::new(3, 2, Topology::Bounded).unwrap();
+        let agent = Fish {
+            coordinate: Point::new(1, 0),
+            decision: Decision::Stall,
+            lifecycle: Lifecycle::fish(0),
+        };
+        let agent_ref = AgentRef::from_fish(agent, AgentId(0));
+        env.set_cell(Point::new(1, 0), Cell::Filled(agent_ref.clone()))
+            .unwrap();
+        env.set_cell(Point::new(2, 1), Cell::Filled(agent_ref))
+            .unwrap();
+
+        assert_eq!(env.debug(), ".f.\n..!\n");
+    }
 }
@@ -0,0 +1,115 @@
+use std::error;
+use std::fmt;
+
+use crate::validation::Problem;
+use crate::Point;
+
+/// Everything that can go wrong while building or altering a simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Width and height must both be strictly positive.
+    InvalidDimensions {
+        width: i32,
+        height: i32,
+    },
+    /// The point lies outside of the environment.
+    OutOfBounds(Point),
+    /// An agent already occupies the cell.
+    CellOccupied(Point),
+    /// The cell was expected to hold an agent.
+    EmptyCell(Point),
+    /// Densities are percentages and cannot exceed 100.
+    InvalidDensity(u8),
+    /// More agents were requested than the environment has cells.
+    PopulationExceedsCapacity {
+        requested: usize,
+        capacity: usize,
+    },
+    UnknownScheduler(String),
+    UnknownConflictPolicy(String),
+    UnknownNeighbourhood(String),
+    UnknownTopology(String),
+    UnknownSnapshotFormat(String),
+    UnknownExportFormat(String),
+    UnknownModel(String),
+    UnknownScenarioFormat(String),
+    UnknownAgentKind(String),
+    UnknownRules(String),
+    UnknownRegrowth(String),
+    /// Statistics could not be written to the export file.
+    Export(String),
+    /// A snapshot could not be written, read or decoded.
+    Snapshot(String),
+    /// A parameter sweep specification cannot be run.
+    InvalidSweep(String),
+    /// A scenario could not be read, decoded or does not describe a valid simulation.
+    InvalidScenario(String),
+    /// Every problem found by a validation pass, see `validation::Validation`.
+    InvalidConfig(Vec<Problem>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidDimensions { width, height } => write!(
+                f,
+                "Invalid dimensions {}x{}, width and height must be greater than 0",
+                width, height
+            ),
+            Error::OutOfBounds(point) => write!(f, "Cell {}:{} is out of bounds", point.x, point.y),
+            Error::CellOccupied(point) => {
+                write!(f, "Agent already stored at {}:{}", point.x, point.y)
+            }
+            Error::EmptyCell(point) => write!(f, "No agent stored at {}:{}", point.x, point.y),
+            Error::InvalidDensity(density) => write!(
+                f,
+                "Density must be inferior or equal to 100, got {}",
+                density
+            ),
+            Error::PopulationExceedsCapacity {
+                requested,
+                capacity,
+            } => write!(
+                f,
+                "Cannot place {} agents in an environment of {} cells",
+                requested, capacity
+            ),
+            Error::UnknownScheduler(name) => write!(f, "Unknown scheduler '{}'", name),
+            Error::UnknownConflictPolicy(name) => write!(f, "Unknown conflict policy '{}'", name),
+            Error::UnknownNeighbourhood(name) => write!(f, "Unknown neighbourhood '{}'", name),
+            Error::UnknownTopology(name) => write!(f, "Unknown topology '{}'", name),
+            Error::UnknownSnapshotFormat(name) => write!(f, "Unknown snapshot format '{}'", name),
+            Error::UnknownExportFormat(name) => write!(f, "Unknown export format '{}'", name),
+            Error::UnknownModel(name) => write!(f, "Unknown model '{}'", name),
+            Error::UnknownScenarioFormat(name) => {
+                write!(
+                    f,
+                    "Unknown scenario format '{}', expected json, toml or ron",
+                    name
+                )
+            }
+            Error::UnknownAgentKind(name) => write!(f, "Unknown agent kind '{}'", name),
+            Error::UnknownRules(name) => write!(f, "Unknown rules '{}'", name),
+            Error::UnknownRegrowth(name) => {
+                write!(
+                    f,
+                    "Unknown regrowth '{}', expected delay:<ticks> or logistic:<rate>",
+                    name
+                )
+            }
+            Error::Export(reason) => write!(f, "Could not export statistics: {}", reason),
+            Error::Snapshot(reason) => write!(f, "Invalid snapshot: {}", reason),
+            Error::InvalidSweep(reason) => write!(f, "Invalid sweep: {}", reason),
+            Error::InvalidScenario(reason) => write!(f, "Invalid scenario: {}", reason),
+            Error::InvalidConfig(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems.iter() {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
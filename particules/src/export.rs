@@ -0,0 +1,211 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::sma::Sma;
+use crate::AgentKind;
+use crate::Error;
+
+/// File format of the per-tick rows written by an `Exporter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Csv, ExportFormat::JsonLines];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        ExportFormat::ALL
+            .iter()
+            .find(|format| format.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownExportFormat(value.into()))
+    }
+}
+
+/// Metrics of a single tick, as written by an `Exporter`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExportRow {
+    pub turn: i32,
+    pub fish: usize,
+    pub sharks: usize,
+    pub particules: usize,
+    pub births: usize,
+    pub deaths: usize,
+    pub starved: u32,
+    pub eaten: u32,
+    /// Mean `HasEnergy::energy` of the sharks, 0 without sharks.
+    pub mean_shark_energy: f64,
+    pub moves: u32,
+    pub stalls: u32,
+    pub collisions: u32,
+    pub bounces: u32,
+    pub conflicts: u32,
+}
+
+impl ExportRow {
+    /// Row of the last tick run by `sma`.
+    pub fn from_sma(sma: &Sma) -> Self {
+        let stats = sma.stats();
+        let energy_levels: Vec<u32> = sma
+            .agents
+            .iter()
+            .filter(|agent| agent.kind() == AgentKind::Shark)
+            .filter_map(|agent| agent.energy())
+            .collect();
+        let mean_shark_energy = if energy_levels.is_empty() {
+            0.0
+        } else {
+            energy_levels.iter().sum::<u32>() as f64 / energy_levels.len() as f64
+        };
+
+        ExportRow {
+            turn: stats.turn,
+            fish: stats.population(AgentKind::Fish),
+            sharks: stats.population(AgentKind::Shark),
+            particules: stats.population(AgentKind::Particule),
+            births: stats.births.len(),
+            deaths: stats.deaths.len(),
+            starved: stats.starved,
+            eaten: stats.eaten,
+            mean_shark_energy,
+            moves: stats.moves,
+            stalls: stats.stalls,
+            collisions: stats.collisions,
+            bounces: stats.bounces,
+            conflicts: stats.conflicts,
+        }
+    }
+}
+
+enum Sink {
+    /// Boxed, the csv writer holds its own buffer.
+    Csv(Box<csv::Writer<Box<dyn Write + Send>>>),
+    JsonLines(Box<dyn Write + Send>),
+}
+
+/// Streams one row per tick once attached to a simulation with `Sma::attach_exporter`.
+pub struct Exporter {
+    sink: Sink,
+}
+
+impl Exporter {
+    pub fn new<W: Write + Send + 'static>(writer: W, format: ExportFormat) -> Self {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let sink = match format {
+            ExportFormat::Csv => Sink::Csv(Box::new(csv::Writer::from_writer(writer))),
+            ExportFormat::JsonLines => Sink::JsonLines(writer),
+        };
+        Exporter { sink }
+    }
+
+    /// Create, or truncate, the file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P, format: ExportFormat) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|err| Error::Export(err.to_string()))?;
+        Ok(Exporter::new(BufWriter::new(file), format))
+    }
+
+    pub fn write(&mut self, row: &ExportRow) -> Result<(), Error> {
+        match &mut self.sink {
+            Sink::Csv(writer) => writer
+                .serialize(row)
+                .map_err(|err| Error::Export(err.to_string())),
+            Sink::JsonLines(writer) => serde_json::to_writer(&mut *writer, row)
+                .map_err(|err| Error::Export(err.to_string()))
+                .and_then(|_| writeln!(writer).map_err(|err| Error::Export(err.to_string()))),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.flush(),
+            Sink::JsonLines(writer) => writer.flush(),
+        }
+        .map_err(|err| Error::Export(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use crate::export::{ExportFormat, Exporter};
+    use crate::sma::Sma;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn export(format: ExportFormat) -> Vec<String> {
+        let buffer = SharedBuffer::default();
+        let mut sma = Sma::new_with_fish(10, 10, 3, 3, 4, 2).unwrap();
+        sma.gen_fish_agents(30, 5).unwrap();
+        sma.attach_exporter(Exporter::new(buffer.clone(), format));
+
+        for _ in 0..3 {
+            sma.tick();
+        }
+        sma.detach_exporter().unwrap().flush().unwrap();
+
+        let content = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        content.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn should_export_csv_rows() {
+        let lines = export(ExportFormat::Csv);
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("turn,fish,sharks,particules,births,deaths,"));
+        assert!(lines[0].contains("mean_shark_energy"));
+        assert!(lines[1].starts_with("0,"));
+        assert!(lines[3].starts_with("2,"));
+    }
+
+    #[test]
+    fn should_export_json_lines() {
+        let lines = export(ExportFormat::JsonLines);
+
+        assert_eq!(lines.len(), 3);
+        for (turn, line) in lines.iter().enumerate() {
+            let row: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(row["turn"], turn);
+            assert!(row["mean_shark_energy"].is_number());
+        }
+    }
+}
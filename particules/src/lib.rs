@@ -1,41 +1,92 @@
 #![feature(vec_remove_item)]
+pub mod conflict;
 pub mod core;
 pub mod environment;
+mod error;
+pub mod export;
+pub mod neighbourhood;
+pub mod observer;
 pub mod particules;
+pub mod resource;
+pub mod scenario;
+pub mod scheduler;
 pub mod sma;
+pub mod snapshot;
+pub mod stats;
+pub mod stop;
+pub mod sweep;
+pub mod topology;
+pub mod validation;
 pub mod wator;
 
-use crate::core::AgentBehavior;
-use crate::sma::Sma;
+pub use crate::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Action, AgentBehavior};
+use crate::snapshot::{AgentRefSnapshot, AgentSnapshot};
 use crate::wator::fish::Fish;
 use crate::wator::shark::Shark;
 use environment::Environment;
-use lazy_static::lazy_static;
 use particules::agent::Agent;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::sync::RwLock;
 
-lazy_static! {
-    pub static ref SMA: Arc<RwLock<Sma>> = {
-        Arc::new(RwLock::new(Sma {
-            env: Environment::new(0, 0, false),
-            agents: vec![],
-            next_generation: vec![],
-            turn: 0,
-        }))
-    };
-}
+/// Random number generator owned by a simulation, seeded at construction so runs can be replayed.
+pub type SmaRng = rand_pcg::Pcg32;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AgentKind {
     Fish,
-    Other
+    Shark,
+    Particule,
+}
+
+impl AgentKind {
+    pub const ALL: [AgentKind; 3] = [AgentKind::Fish, AgentKind::Shark, AgentKind::Particule];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            AgentKind::Fish => "fish",
+            AgentKind::Shark => "shark",
+            AgentKind::Particule => "particule",
+        }
+    }
+}
+
+impl fmt::Display for AgentKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for AgentKind {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        AgentKind::ALL
+            .iter()
+            .find(|kind| kind.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownAgentKind(value.into()))
+    }
 }
-#[derive(Clone)]
+
+/// Why an agent was removed from the simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Starvation,
+    Eaten,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "AgentRefSnapshot", from = "AgentRefSnapshot")]
 pub struct AgentRef {
+    id: AgentId,
     inner: Arc<Mutex<Box<dyn AgentBehavior + Sync + Send>>>,
-    marked_for_removal: Arc<Mutex<bool>>,
+    marked_for_removal: Arc<Mutex<Option<DeathCause>>>,
     kind: AgentKind
 }
 
@@ -45,12 +96,24 @@ pub enum AgentCommand {
 }
 
 impl AgentRef {
-    pub fn collision(&self) -> bool {
-        self.inner.lock().unwrap().collision()
+    pub fn id(&self) -> AgentId {
+        self.id
     }
 
-    pub fn direction(&self) -> Direction {
-        self.inner.lock().unwrap().direction()
+    pub fn kind(&self) -> AgentKind {
+        self.kind
+    }
+
+    /// Whether the agent just bumped into something, `None` if it cannot collide.
+    pub fn collision(&self) -> Option<bool> {
+        let inner = self.inner.lock().unwrap();
+        inner.as_collidable().map(|agent| agent.collision())
+    }
+
+    /// Heading of the agent, `None` if it does not move along a direction.
+    pub fn direction(&self) -> Option<Direction> {
+        let inner = self.inner.lock().unwrap();
+        inner.as_directed().map(|agent| agent.direction())
     }
 
     pub fn set_coordinate(&self, point: Point) {
@@ -61,66 +124,136 @@ impl AgentRef {
         self.inner.lock().unwrap().coordinate()
     }
 
-    pub fn previous_coordinate(&self) -> Point {
-        self.inner.lock().unwrap().previous_coordinate()
+    pub fn previous_coordinate(&self) -> Option<Point> {
+        let inner = self.inner.lock().unwrap();
+        inner.as_directed().map(|agent| agent.previous_coordinate())
     }
 
+    /// Does nothing on agents that cannot collide.
     pub fn set_collision(&self, collision: bool) {
-        self.inner.lock().unwrap().set_collision(collision)
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(agent) = inner.as_collidable_mut() {
+            agent.set_collision(collision)
+        }
     }
 
+    /// Does nothing on agents that do not move along a direction.
     pub fn set_direction(&self, direction: Direction) {
-        self.inner.lock().unwrap().set_direction(direction)
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(agent) = inner.as_directed_mut() {
+            agent.set_direction(direction)
+        }
+    }
+
+    /// Ticks left before starvation, `None` if the agent cannot starve.
+    pub fn energy(&self) -> Option<u32> {
+        let inner = self.inner.lock().unwrap();
+        inner.as_energy().map(|agent| agent.energy())
+    }
+
+    /// Ticks left before the next birth, `None` if the agent does not breed.
+    pub fn breed_count_down(&self) -> Option<u32> {
+        let inner = self.inner.lock().unwrap();
+        inner.as_breeds().map(|agent| agent.breed_count_down())
     }
 
     pub fn update(&mut self, env: &mut Environment) -> AgentCommand {
         self.inner.lock().unwrap().update(env)
     }
 
-    pub fn decide(&mut self, env: &Environment) {
-        self.inner.lock().unwrap().decide(env)
+    pub fn update_synchronous(&mut self, env: &mut Environment) -> AgentCommand {
+        self.inner.lock().unwrap().update_synchronous(env)
+    }
+
+    pub fn decide(&mut self, env: &Environment, rng: &mut SmaRng) {
+        self.inner.lock().unwrap().decide(env, rng)
+    }
+
+    pub fn target(&self, env: &Environment) -> Option<Point> {
+        self.inner.lock().unwrap().target(env)
+    }
+
+    pub fn reach(&self, env: &Environment) -> Option<Point> {
+        self.inner.lock().unwrap().reach(env)
+    }
+
+    pub fn stall(&mut self) {
+        self.inner.lock().unwrap().stall()
+    }
+
+    pub fn last_action(&self) -> Action {
+        self.inner.lock().unwrap().last_action()
+    }
+
+    pub fn snapshot(&self) -> AgentSnapshot {
+        self.inner.lock().unwrap().snapshot()
     }
 
     pub fn get_color(&self) -> (f32, f32, f32) {
         self.inner.lock().unwrap().get_color()
     }
 
-    pub fn mark_for_removal(&mut self) {
-        self.marked_for_removal = Arc::new(Mutex::new(true));
+    pub fn mark_for_removal(&mut self, cause: DeathCause) {
+        *self.marked_for_removal.lock().unwrap() = Some(cause);
+    }
+
+    pub fn is_marked_for_removal(&self) -> bool {
+        self.death_cause().is_some()
+    }
+
+    pub fn death_cause(&self) -> Option<DeathCause> {
+        *self.marked_for_removal.lock().unwrap()
     }
 
-    pub fn from_fish(agent: Fish) -> Self {
+    pub(crate) fn is_same(&self, other: &AgentRef) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub fn from_fish(agent: Fish, id: AgentId) -> Self {
         AgentRef {
+            id,
             inner: Arc::new(Mutex::new(Box::new(agent))),
-            marked_for_removal: Arc::new(Mutex::new(false)),
+            marked_for_removal: Arc::new(Mutex::new(None)),
             kind: AgentKind::Fish
         }
     }
 
-    pub fn from_shark(agent: Shark) -> Self {
+    pub fn from_shark(agent: Shark, id: AgentId) -> Self {
         AgentRef {
+            id,
             inner: Arc::new(Mutex::new(Box::new(agent))),
-            marked_for_removal:  Arc::new(Mutex::new(false)),
-            kind: AgentKind::Other
+            marked_for_removal: Arc::new(Mutex::new(None)),
+            kind: AgentKind::Shark
         }
     }
 
-    pub fn from(agent: Agent) -> Self {
+    pub fn from(agent: Agent, id: AgentId) -> Self {
         AgentRef {
+            id,
             inner: Arc::new(Mutex::new(Box::new(agent))),
-            marked_for_removal:  Arc::new(Mutex::new(false)),
-            kind: AgentKind::Other
+            marked_for_removal: Arc::new(Mutex::new(None)),
+            kind: AgentKind::Particule
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Unique identifier given to an agent when it is created, in creation order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct AgentId(pub u64);
+
+impl fmt::Display for AgentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Direction {
     pub x: HDirection,
     pub y: VDirection,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -138,7 +271,7 @@ impl Direction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HDirection {
     None,
     Right,
@@ -155,7 +288,7 @@ impl HDirection {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VDirection {
     None,
     Down,
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Set of cells an agent looks at around itself, as offsets from its own position.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Neighbourhood {
+    /// The eight surrounding cells.
+    #[default]
+    Moore,
+    /// The four orthogonally adjacent cells.
+    VonNeumann,
+    /// Every cell within `r` steps in both directions, `ExtendedMoore(1)` is `Moore`.
+    ExtendedMoore(u8),
+    /// Arbitrary offsets, `(0, 0)` is ignored.
+    Custom(Vec<(i32, i32)>),
+}
+
+impl Neighbourhood {
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighbourhood::Moore => Neighbourhood::square(1),
+            Neighbourhood::VonNeumann => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighbourhood::ExtendedMoore(radius) => Neighbourhood::square(*radius as i32),
+            Neighbourhood::Custom(offsets) => offsets
+                .iter()
+                .cloned()
+                .filter(|offset| *offset != (0, 0))
+                .collect(),
+        }
+    }
+
+    fn square(radius: i32) -> Vec<(i32, i32)> {
+        let mut offsets = vec![];
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                if (x, y) != (0, 0) {
+                    offsets.push((x, y));
+                }
+            }
+        }
+        offsets
+    }
+}
+
+impl fmt::Display for Neighbourhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Neighbourhood::Moore => write!(f, "moore"),
+            Neighbourhood::VonNeumann => write!(f, "von_neumann"),
+            Neighbourhood::ExtendedMoore(radius) => write!(f, "moore:{}", radius),
+            Neighbourhood::Custom(offsets) => write!(f, "custom:{:?}", offsets),
+        }
+    }
+}
+
+/// Parses `moore`, `von_neumann` and `moore:<radius>`, custom masks are only built in code.
+impl FromStr for Neighbourhood {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "moore" => Ok(Neighbourhood::Moore),
+            "von_neumann" => Ok(Neighbourhood::VonNeumann),
+            _ if value.starts_with("moore:") => value["moore:".len()..]
+                .parse()
+                .map(Neighbourhood::ExtendedMoore)
+                .map_err(|_| Error::UnknownNeighbourhood(value.into())),
+            _ => Err(Error::UnknownNeighbourhood(value.into())),
+        }
+    }
+}
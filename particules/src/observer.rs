@@ -0,0 +1,32 @@
+use crate::stats::TickStats;
+use crate::AgentRef;
+use crate::DeathCause;
+use crate::Point;
+
+/// Receives the events of a simulation once registered with `Sma::add_observer`.
+///
+/// Every callback does nothing by default, observers only implement the events they need.
+pub trait Observer {
+    fn on_tick_start(&mut self, _turn: i32) {}
+
+    /// Called once the newborn is placed on the grid, at the end of the scheduler step.
+    fn on_agent_born(&mut self, _agent: &AgentRef) {}
+
+    /// `agent` still holds the coordinate of its last known cell.
+    fn on_agent_died(&mut self, _agent: &AgentRef, _cause: DeathCause) {}
+
+    fn on_agent_moved(&mut self, _agent: &AgentRef, _from: Point, _to: Point) {}
+
+    fn on_collision(&mut self, _agent: &AgentRef) {}
+
+    fn on_tick_end(&mut self, _stats: &TickStats) {}
+}
+
+pub(crate) fn notify<F>(observers: &mut [Box<dyn Observer + Send>], event: F)
+where
+    F: Fn(&mut dyn Observer),
+{
+    for observer in observers.iter_mut() {
+        event(observer.as_mut());
+    }
+}
@@ -1,10 +1,14 @@
-use crate::core::AgentBehavior;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Action, AgentBehavior, Collidable, Directed};
 use crate::environment::Cell;
 use crate::environment::Environment;
+use crate::snapshot::AgentSnapshot;
 use crate::Direction;
 use crate::Point;
-use crate::{AgentCommand, AgentRef};
+use crate::{AgentCommand, SmaRng};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Agent {
     pub direction: Direction,
     pub coordinate: Point,
@@ -13,30 +17,41 @@ pub struct Agent {
     pub(crate) decision: Decision,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum Decision {
     KeepCourse,
-    ChangeCourseCollision(AgentRef),
+    /// Bump into the agent standing on that cell and head on with the direction, that of
+    /// the other agent when it has one.
+    ChangeCourseCollision(Point, Direction),
     ChangeCourseOutOfBound(Direction),
+    Stall,
 }
 
 impl AgentBehavior for Agent {
-    fn decide(&mut self, environment: &Environment) {
-        let forward_position = if environment.borderless {
-            self.look_ahead_borderless(environment.width, environment.height)
-        } else {
-            self.look_ahead()
-        };
+    fn decide(&mut self, environment: &Environment, _: &mut SmaRng) {
+        let forward_position = self.look_ahead();
 
         let out_of_bound_x = environment.is_out_of_bound_x(forward_position.x);
         let out_of_bound_y = environment.is_out_of_bound_y(forward_position.y);
 
         self.decision = if !out_of_bound_x && !out_of_bound_y {
+            // Wrapped axes are never out of bound, the index follows the topology
             let forward_idx = environment.get_index(forward_position);
             let cell_forward = &environment.cells[forward_idx];
 
             match cell_forward {
                 Cell::Empty => Decision::KeepCourse,
-                Cell::Filled(agent) => Decision::ChangeCourseCollision(agent.clone()),
+                // A one cell wide wrapped world leads the agent back onto itself
+                Cell::Filled(_) if forward_idx == environment.get_index(self.coordinate) => {
+                    Decision::Stall
+                }
+                Cell::Filled(other) => {
+                    // Bounce straight back on agents that have no direction to trade
+                    let bounce = other.direction().unwrap_or_else(|| {
+                        Direction::new(self.direction.x.invert(), self.direction.y.invert())
+                    });
+                    Decision::ChangeCourseCollision(forward_position, bounce)
+                }
             }
         } else if out_of_bound_x && !out_of_bound_y {
             Decision::ChangeCourseOutOfBound(Direction::new(
@@ -60,16 +75,20 @@ impl AgentBehavior for Agent {
 
     fn update(&mut self, environment: &mut Environment) -> AgentCommand {
         match &self.decision {
+            Decision::Stall => self.collision = false,
             Decision::ChangeCourseOutOfBound(direction) => {
                 self.direction.y = direction.y;
                 self.direction.x = direction.x;
                 self.collision = true;
             }
-            Decision::ChangeCourseCollision(agent) if !environment.borderless => {
+            Decision::ChangeCourseCollision(position, bounce) => {
                 let direction = self.direction;
-                self.direction = agent.direction();
-                agent.set_direction(direction);
-                agent.set_collision(true);
+                self.direction = *bounce;
+                // The other agent heads on with ours, it decides after this update
+                if let Some(Cell::Filled(agent)) = environment.get_cell(*position) {
+                    agent.set_direction(direction);
+                    agent.set_collision(true);
+                }
                 self.collision = true;
             }
             _ => {
@@ -80,12 +99,17 @@ impl AgentBehavior for Agent {
         AgentCommand::DoNothing
     }
 
-    fn collision(&self) -> bool {
-        self.collision
-    }
-
-    fn direction(&self) -> Direction {
-        self.direction
+    fn update_synchronous(&mut self, environment: &mut Environment) -> AgentCommand {
+        match self.decision {
+            // The other agent took its own decision on the same environment, in a head-on
+            // collision it bounces back by itself
+            Decision::ChangeCourseCollision(_, bounce) => {
+                self.direction = bounce;
+                self.collision = true;
+                AgentCommand::DoNothing
+            }
+            _ => self.update(environment),
+        }
     }
 
     fn coordinate(&self) -> Point {
@@ -96,27 +120,88 @@ impl AgentBehavior for Agent {
         self.coordinate = point
     }
 
-    fn previous_coordinate(&self) -> Point {
-        self.previous_coordinate
+    fn get_color(&self) -> (f32, f32, f32) {
+        if self.collision {
+            (1.0, 0.0, 0.0)
+        } else {
+            (0.0, 0.0, 0.0)
+        }
     }
 
-    fn set_previous_coordinate(&mut self, point: Point) {
-        self.previous_coordinate = point
+    fn target(&self, environment: &Environment) -> Option<Point> {
+        match self.decision {
+            Decision::KeepCourse => environment.normalize(self.look_ahead()),
+            _ => None,
+        }
     }
 
-    fn set_collision(&mut self, collision: bool) {
-        self.collision = collision
+    fn reach(&self, environment: &Environment) -> Option<Point> {
+        match self.decision {
+            // The agent bumped into turns red as well
+            Decision::ChangeCourseCollision(position, _) => Some(position),
+            _ => self.target(environment),
+        }
+    }
+
+    fn stall(&mut self) {
+        self.decision = Decision::Stall;
+    }
+
+    fn last_action(&self) -> Action {
+        match self.decision {
+            // The cell ahead may have been taken after the decision
+            Decision::KeepCourse if self.coordinate != self.previous_coordinate => Action::Moved,
+            Decision::KeepCourse | Decision::Stall => Action::Stalled,
+            Decision::ChangeCourseCollision(..) => Action::Collided,
+            Decision::ChangeCourseOutOfBound(_) => Action::Bounced,
+        }
+    }
+
+    fn snapshot(&self) -> AgentSnapshot {
+        AgentSnapshot::Particule(self.clone())
+    }
+
+    fn as_directed(&self) -> Option<&dyn Directed> {
+        Some(self)
+    }
+
+    fn as_directed_mut(&mut self) -> Option<&mut dyn Directed> {
+        Some(self)
+    }
+
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        Some(self)
+    }
+
+    fn as_collidable_mut(&mut self) -> Option<&mut dyn Collidable> {
+        Some(self)
+    }
+}
+
+impl Directed for Agent {
+    fn direction(&self) -> Direction {
+        self.direction
     }
 
     fn set_direction(&mut self, direction: Direction) {
         self.direction = direction
     }
 
-    fn get_color(&self) -> (f32, f32, f32) {
-        if self.collision {
-            (1.0, 0.0, 0.0)
-        } else {
-            (0.0, 0.0, 0.0)
-        }
+    fn previous_coordinate(&self) -> Point {
+        self.previous_coordinate
+    }
+
+    fn set_previous_coordinate(&mut self, point: Point) {
+        self.previous_coordinate = point
+    }
+}
+
+impl Collidable for Agent {
+    fn collision(&self) -> bool {
+        self.collision
+    }
+
+    fn set_collision(&mut self, collision: bool) {
+        self.collision = collision
     }
 }
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Share of the capacity a logistic cell grows back from once eaten out, without it an
+/// empty cell would never grow again.
+const LOGISTIC_SEED: f32 = 0.01;
+
+/// How eaten cells grow back.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Regrowth {
+    /// An eaten cell is back to full capacity `delay` ticks later, like the grass of the
+    /// wolf–sheep–grass model.
+    Delay(u32),
+    /// Every cell grows by `rate * level * (1 - level / capacity)` on each tick.
+    Logistic(f32),
+}
+
+impl Default for Regrowth {
+    fn default() -> Self {
+        Regrowth::Delay(30)
+    }
+}
+
+impl fmt::Display for Regrowth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Regrowth::Delay(delay) => write!(f, "delay:{}", delay),
+            Regrowth::Logistic(rate) => write!(f, "logistic:{}", rate),
+        }
+    }
+}
+
+/// Parses `delay:<ticks>` and `logistic:<rate>`.
+impl FromStr for Regrowth {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let unknown = || Error::UnknownRegrowth(value.into());
+        if let Some(delay) = value.strip_prefix("delay:") {
+            delay.parse().map(Regrowth::Delay).map_err(|_| unknown())
+        } else if let Some(rate) = value.strip_prefix("logistic:") {
+            rate.parse().map(Regrowth::Logistic).map_err(|_| unknown())
+        } else {
+            Err(unknown())
+        }
+    }
+}
+
+/// Parameters of the resource layer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceRules {
+    /// Quantity held by a full cell.
+    pub capacity: f32,
+    #[serde(with = "crate::scenario::by_name")]
+    pub regrowth: Regrowth,
+    /// Eaten by a fish from the cell it stands on, on every tick.
+    pub bite: f32,
+}
+
+impl Default for ResourceRules {
+    fn default() -> Self {
+        ResourceRules {
+            capacity: 1.0,
+            regrowth: Regrowth::default(),
+            bite: 1.0,
+        }
+    }
+}
+
+/// Food growing on every cell of the environment, grass or plankton for the fish.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLayer {
+    pub rules: ResourceRules,
+    levels: Vec<f32>,
+    /// Ticks left before an eaten cell grows back, `Regrowth::Delay` only.
+    timers: Vec<u32>,
+}
+
+impl ResourceLayer {
+    /// Each of the `size` cells starts full.
+    pub fn new(size: usize, rules: ResourceRules) -> Self {
+        ResourceLayer {
+            levels: vec![rules.capacity; size],
+            timers: vec![0; size],
+            rules,
+        }
+    }
+
+    pub fn level(&self, index: usize) -> f32 {
+        self.levels[index]
+    }
+
+    /// Share of the capacity left on the cell, between 0 and 1.
+    pub fn fraction(&self, index: usize) -> f32 {
+        self.levels[index] / self.rules.capacity
+    }
+
+    /// Take a bite out of the cell, returns the quantity actually eaten.
+    pub fn consume(&mut self, index: usize) -> f32 {
+        let eaten = self.rules.bite.min(self.levels[index]).max(0.0);
+        self.levels[index] -= eaten;
+        if let Regrowth::Delay(delay) = self.rules.regrowth {
+            if eaten > 0.0 {
+                self.timers[index] = delay;
+            }
+        }
+        eaten
+    }
+
+    /// One tick of regrowth on every cell, returns the index and previous fraction of the
+    /// cells whose `tint` changed.
+    pub fn grow(&mut self) -> Vec<(usize, f32)> {
+        let capacity = self.rules.capacity;
+        let mut repainted = vec![];
+        let cells = self.levels.iter_mut().zip(self.timers.iter_mut());
+        for (index, (level, timer)) in cells.enumerate() {
+            if *level >= capacity {
+                continue;
+            }
+            let previous = *level;
+            match self.rules.regrowth {
+                Regrowth::Delay(_) => {
+                    *timer = timer.saturating_sub(1);
+                    if *timer == 0 {
+                        *level = capacity;
+                    }
+                }
+                Regrowth::Logistic(rate) => {
+                    let seed = level.max(capacity * LOGISTIC_SEED);
+                    *level = (*level + rate * seed * (1.0 - *level / capacity)).min(capacity);
+                }
+            }
+            if tint(previous / capacity) != tint(*level / capacity) {
+                repainted.push((index, previous / capacity));
+            }
+        }
+        repainted
+    }
+}
+
+/// Colour of an empty cell holding `fraction` of its capacity, from white to pale green.
+pub fn tint(fraction: f32) -> (f32, f32, f32) {
+    // Ten shades keep slowly growing cells from being repainted on every tick
+    let fraction = (fraction.clamp(0.0, 1.0) * 10.0).round() / 10.0;
+    (
+        1.0 - 0.3 * fraction,
+        1.0 - 0.1 * fraction,
+        1.0 - 0.3 * fraction,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{Regrowth, ResourceLayer, ResourceRules};
+
+    #[test]
+    fn should_parse_regrowth() {
+        for regrowth in [Regrowth::Delay(5), Regrowth::Logistic(0.5)].iter() {
+            assert_eq!(regrowth.to_string().parse::<Regrowth>(), Ok(*regrowth));
+        }
+        assert!("delay".parse::<Regrowth>().is_err());
+        assert!("logistic:fast".parse::<Regrowth>().is_err());
+    }
+
+    #[test]
+    fn should_grow_back_after_delay() {
+        let rules = ResourceRules {
+            regrowth: Regrowth::Delay(3),
+            ..ResourceRules::default()
+        };
+        let mut layer = ResourceLayer::new(2, rules);
+
+        assert_eq!(layer.consume(0), 1.0);
+        assert_eq!(layer.consume(0), 0.0);
+        for _ in 0..2 {
+            layer.grow();
+            assert_eq!(layer.level(0), 0.0);
+        }
+        layer.grow();
+        assert_eq!(layer.level(0), 1.0);
+        assert_eq!(layer.level(1), 1.0);
+    }
+
+    #[test]
+    fn should_grow_logistically() {
+        let rules = ResourceRules {
+            capacity: 10.0,
+            regrowth: Regrowth::Logistic(0.5),
+            bite: 10.0,
+        };
+        let mut layer = ResourceLayer::new(1, rules);
+        layer.consume(0);
+
+        let mut levels = vec![];
+        for _ in 0..30 {
+            layer.grow();
+            levels.push(layer.level(0));
+        }
+        assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(levels[0] > 0.0 && levels[0] < 1.0);
+        assert!(levels[29] > 9.9 && levels[29] <= 10.0);
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conflict::ConflictPolicy;
+use crate::neighbourhood::Neighbourhood;
+use crate::resource::{Regrowth, ResourceRules};
+use crate::scheduler::Scheduler;
+use crate::sma::Sma;
+use crate::topology::Topology;
+use crate::validation::Validation;
+use crate::wator::energy::{EnergyRules, Rules};
+use crate::AgentKind;
+use crate::Direction;
+use crate::Error;
+use crate::Point;
+
+/// Simulation run by a scenario.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Model {
+    /// Particules bouncing off walls and each other.
+    Particules,
+    /// Fish and sharks.
+    #[default]
+    Wator,
+}
+
+impl Model {
+    pub const ALL: [Model; 2] = [Model::Particules, Model::Wator];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Model::Particules => "particules",
+            Model::Wator => "wator",
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Model {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Model::ALL
+            .iter()
+            .find(|model| model.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownModel(value.into()))
+    }
+}
+
+/// Encoding of a scenario file, guessed from its extension by `Scenario::load`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScenarioFormat {
+    #[default]
+    Json,
+    Toml,
+    Ron,
+}
+
+impl ScenarioFormat {
+    pub const ALL: [ScenarioFormat; 3] = [
+        ScenarioFormat::Json,
+        ScenarioFormat::Toml,
+        ScenarioFormat::Ron,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ScenarioFormat::Json => "json",
+            ScenarioFormat::Toml => "toml",
+            ScenarioFormat::Ron => "ron",
+        }
+    }
+}
+
+impl fmt::Display for ScenarioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ScenarioFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        ScenarioFormat::ALL
+            .iter()
+            .find(|format| format.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownScenarioFormat(value.into()))
+    }
+}
+
+/// Agent placed at a given cell before the random population is generated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    #[serde(with = "by_name")]
+    pub kind: AgentKind,
+    pub x: i32,
+    pub y: i32,
+    /// Heading of a particule, random if omitted.
+    #[serde(default)]
+    pub direction: Option<Direction>,
+}
+
+/// Largest width or height of a scenario.
+pub const MAX_SIDE: i32 = 10_000;
+
+/// Everything needed to set up a simulation, shared by every front-end.
+///
+/// Missing fields take their default value, enumerations are written by name
+/// (`"torus"`, `"moore:2"`...) as on the command line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    #[serde(with = "by_name")]
+    pub model: Model,
+    pub width: i32,
+    pub height: i32,
+    #[serde(with = "by_name")]
+    pub topology: Topology,
+    #[serde(with = "by_name")]
+    pub neighbourhood: Neighbourhood,
+    #[serde(with = "by_name")]
+    pub scheduler: Scheduler,
+    #[serde(with = "by_name")]
+    pub conflict_policy: ConflictPolicy,
+    /// Percentage of cells holding a particule.
+    pub density: u8,
+    /// Number of fish placed at random.
+    pub fish_density: u8,
+    /// Number of sharks placed at random.
+    pub shark_density: u8,
+    pub fish_breed_time: u8,
+    pub shark_breed_time: u8,
+    pub shark_starve_time: u8,
+    /// How sharks survive and breed.
+    #[serde(with = "by_name")]
+    pub rules: Rules,
+    /// Parameters of `Rules::Energy`.
+    pub energy: EnergyRules,
+    /// Resource layer eaten by the fish, none if omitted.
+    pub resources: Option<ResourceRules>,
+    /// Place fish and sharks at a random point of their breeding cycle, rather than all
+    /// giving birth on the same tick.
+    pub random_age: bool,
+    /// Random if omitted.
+    pub seed: Option<u64>,
+    pub placements: Vec<Placement>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            model: Model::default(),
+            width: 100,
+            height: 100,
+            topology: Topology::default(),
+            neighbourhood: Neighbourhood::default(),
+            scheduler: Scheduler::default(),
+            conflict_policy: ConflictPolicy::default(),
+            density: 10,
+            fish_density: 200,
+            shark_density: 20,
+            fish_breed_time: 3,
+            shark_breed_time: 10,
+            shark_starve_time: 3,
+            rules: Rules::default(),
+            energy: EnergyRules::default(),
+            resources: None,
+            random_age: false,
+            seed: None,
+            placements: vec![],
+        }
+    }
+}
+
+impl Scenario {
+    /// Read and validate the scenario at `path`, its format is given by the file extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scenario, Error> {
+        let scenario = Scenario::read(path)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Read the scenario at `path` without validating it, so that it can be amended first.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Scenario, Error> {
+        let path = path.as_ref();
+        let format = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .parse()?;
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::InvalidScenario(format!("{}: {}", path.display(), err)))?;
+        Scenario::decode(&content, format)
+    }
+
+    /// Decode and validate a scenario.
+    pub fn parse(content: &str, format: ScenarioFormat) -> Result<Scenario, Error> {
+        let scenario = Scenario::decode(content, format)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn decode(content: &str, format: ScenarioFormat) -> Result<Scenario, Error> {
+        match format {
+            ScenarioFormat::Json => serde_json::from_str(content).map_err(|err| err.to_string()),
+            ScenarioFormat::Toml => toml::from_str(content).map_err(|err| err.to_string()),
+            ScenarioFormat::Ron => ron::de::from_str(content).map_err(|err| err.to_string()),
+        }
+        .map_err(Error::InvalidScenario)
+    }
+
+    /// `Error::InvalidConfig` listing every problem of the scenario.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut validation = Validation::new();
+        self.check(&mut validation);
+        validation.finish()
+    }
+
+    /// Report every problem of the scenario, alongside those of the caller's own settings.
+    pub fn check(&self, validation: &mut Validation) {
+        let width = validation.check_range("width", self.width, 1, MAX_SIDE);
+        let height = validation.check_range("height", self.height, 1, MAX_SIDE);
+        let density = match self.model {
+            Model::Particules => validation.check_range("density", self.density, 0, 100),
+            Model::Wator => {
+                validation.check_min("fish_breed_time", self.fish_breed_time, 1);
+                match self.rules {
+                    Rules::Classic => {
+                        validation.check_min("shark_breed_time", self.shark_breed_time, 1);
+                        validation.check_min("shark_starve_time", self.shark_starve_time, 1);
+                    }
+                    Rules::Energy => {
+                        let energy = &self.energy;
+                        validation.check_min("energy.initial_energy", energy.initial_energy, 1);
+                        // Both halves of a split must stay alive
+                        validation.check_min("energy.breed_threshold", energy.breed_threshold, 2);
+                    }
+                }
+                if let Some(resources) = &self.resources {
+                    if resources.capacity <= 0.0 {
+                        let message = format!("must be greater than 0, got {}", resources.capacity);
+                        validation.report("resources.capacity", message);
+                    }
+                    validation.check_min("resources.bite", resources.bite, 0.0);
+                    if let Regrowth::Logistic(rate) = resources.regrowth {
+                        validation.check_range("resources.regrowth", rate, 0.0, 1.0);
+                    }
+                }
+                true
+            }
+        };
+        if !width || !height {
+            return;
+        }
+
+        let mut occupied = HashMap::new();
+        for (i, placement) in self.placements.iter().enumerate() {
+            let field = |name: &str| format!("placements[{}].{}", i, name);
+            let x = validation.check_range(field("x"), placement.x, 0, self.width - 1);
+            let y = validation.check_range(field("y"), placement.y, 0, self.height - 1);
+            if x && y {
+                if let Some(first) = occupied.insert((placement.x, placement.y), i) {
+                    let message = format!(
+                        "cell {}:{} is already taken by placements[{}]",
+                        placement.x, placement.y, first
+                    );
+                    validation.report(format!("placements[{}]", i), message);
+                }
+            }
+            let expected = match self.model {
+                Model::Particules => placement.kind == AgentKind::Particule,
+                Model::Wator => placement.kind != AgentKind::Particule,
+            };
+            if !expected {
+                let message = format!(
+                    "a {} cannot be placed in a {} scenario",
+                    placement.kind, self.model
+                );
+                validation.report(field("kind"), message);
+            }
+        }
+
+        let cells = (self.width * self.height) as usize;
+        let capacity = cells - occupied.len();
+        match self.model {
+            Model::Particules => {
+                let requested = cells * self.density as usize / 100;
+                if density && requested > capacity {
+                    let message = format!(
+                        "{} particules do not fit in the {} free cells, lower the density",
+                        requested, capacity
+                    );
+                    validation.report("density", message);
+                }
+            }
+            Model::Wator => {
+                let requested = self.fish_density as usize + self.shark_density as usize;
+                if requested > capacity {
+                    let message =
+                        format!("must be at most {} free cells, got {}", capacity, requested);
+                    validation.report("fish_density + shark_density", message);
+                }
+            }
+        }
+    }
+
+    /// Configured simulation without any agent.
+    pub fn empty(&self) -> Result<Sma, Error> {
+        let seed = self.seed.unwrap_or_else(Sma::random_seed);
+        let mut sma = match self.model {
+            Model::Particules => Sma::new(self.width, self.height, seed)?,
+            Model::Wator => Sma::new_with_fish(
+                self.width,
+                self.height,
+                self.fish_breed_time,
+                self.shark_breed_time,
+                self.shark_starve_time,
+                seed,
+            )?,
+        };
+        sma.set_topology(self.topology);
+        sma.set_neighbourhood(self.neighbourhood.clone());
+        sma.set_scheduler(self.scheduler);
+        sma.set_conflict_policy(self.conflict_policy);
+        if self.rules == Rules::Energy {
+            sma.set_energy_rules(Some(self.energy.clone()));
+        }
+        if self.model == Model::Wator {
+            sma.set_resources(self.resources.clone());
+        }
+        sma.set_random_age(self.random_age);
+        Ok(sma)
+    }
+
+    /// Configured simulation holding the placed agents, then the random population.
+    pub fn build(&self) -> Result<Sma, Error> {
+        self.validate()?;
+        let mut sma = self.empty()?;
+
+        for placement in self.placements.iter() {
+            let point = Point::new(placement.x, placement.y);
+            match placement.kind {
+                AgentKind::Fish => sma.gen_fish(point)?,
+                AgentKind::Shark => sma.gen_shark(point)?,
+                AgentKind::Particule => {
+                    let direction = placement
+                        .direction
+                        .unwrap_or_else(|| sma.random_direction());
+                    sma.gen_agent(point, direction)?
+                }
+            };
+        }
+
+        match self.model {
+            Model::Particules => sma.gen_agents(self.density)?,
+            Model::Wator => sma.gen_fish_agents(self.fish_density, self.shark_density)?,
+        }
+        Ok(sma)
+    }
+}
+
+/// (De)serialize a value through its `Display` and `FromStr` implementations.
+pub(crate) mod by_name {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{Regrowth, ResourceRules};
+    use crate::scenario::{Model, Scenario, ScenarioFormat};
+    use crate::topology::Topology;
+    use crate::wator::energy::{EnergyRules, Rules};
+    use crate::AgentKind;
+    use crate::Error;
+    use crate::Point;
+
+    #[test]
+    fn should_parse_every_format() {
+        let json = r#"{
+            "width": 10, "height": 8, "topology": "torus", "seed": 3,
+            "fish_density": 5, "shark_density": 2,
+            "placements": [{"kind": "shark", "x": 1, "y": 2}]
+        }"#;
+        let toml = r#"
+            width = 10
+            height = 8
+            topology = "torus"
+            seed = 3
+            fish_density = 5
+            shark_density = 2
+
+            [[placements]]
+            kind = "shark"
+            x = 1
+            y = 2
+        "#;
+        let ron = r#"(
+            width: 10, height: 8, topology: "torus", seed: Some(3),
+            fish_density: 5, shark_density: 2,
+            placements: [(kind: "shark", x: 1, y: 2)],
+        )"#;
+
+        let scenario = Scenario::parse(json, ScenarioFormat::Json).unwrap();
+        assert_eq!(scenario.model, Model::Wator);
+        assert_eq!(scenario.topology, Topology::Torus);
+        assert_eq!(scenario.placements[0].kind, AgentKind::Shark);
+        assert_eq!(
+            Scenario::parse(toml, ScenarioFormat::Toml).unwrap(),
+            scenario
+        );
+        assert_eq!(Scenario::parse(ron, ScenarioFormat::Ron).unwrap(), scenario);
+    }
+
+    #[test]
+    fn should_reject_invalid_scenarios() {
+        let problems = |json| match Scenario::parse(json, ScenarioFormat::Json) {
+            Err(Error::InvalidConfig(problems)) => problems
+                .into_iter()
+                .map(|problem| problem.to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("expected validation problems, got {:?}", other),
+        };
+
+        assert_eq!(
+            problems(r#"{"width": 0, "fish_breed_time": 0}"#),
+            vec![
+                "width: must be between 1 and 10000, got 0",
+                "fish_breed_time: must be at least 1, got 0",
+            ]
+        );
+        assert_eq!(
+            problems(r#"{"placements": [{"kind": "fish", "x": 100, "y": 0}]}"#),
+            vec!["placements[0].x: must be between 0 and 99, got 100"]
+        );
+        assert_eq!(
+            problems(r#"{"width": 2, "height": 2, "fish_density": 3, "shark_density": 2}"#),
+            vec!["fish_density + shark_density: must be at most 4 free cells, got 5"]
+        );
+        assert_eq!(
+            problems(
+                r#"{"model": "particules", "density": 101, "placements": [
+                    {"kind": "fish", "x": 0, "y": 0},
+                    {"kind": "particule", "x": 0, "y": 0}
+                ]}"#
+            ),
+            vec![
+                "density: must be between 0 and 100, got 101",
+                "placements[0].kind: a fish cannot be placed in a particules scenario",
+                "placements[1]: cell 0:0 is already taken by placements[0]",
+            ]
+        );
+        assert_eq!(
+            problems(
+                r#"{"rules": "energy", "shark_starve_time": 0, "energy": {"breed_threshold": 1}}"#
+            ),
+            vec!["energy.breed_threshold: must be at least 2, got 1"]
+        );
+        assert!(Scenario::parse(r#"{"topology": "sphere"}"#, ScenarioFormat::Json).is_err());
+        assert_eq!(
+            problems(r#"{"resources": {"capacity": 0, "regrowth": "logistic:2"}}"#),
+            vec![
+                "resources.capacity: must be greater than 0, got 0",
+                "resources.regrowth: must be between 0 and 1, got 2",
+            ]
+        );
+        assert!(Scenario::parse(r#"{"rules": "fuel"}"#, ScenarioFormat::Json).is_err());
+        let rain = r#"{"resources": {"regrowth": "rain"}}"#;
+        assert!(Scenario::parse(rain, ScenarioFormat::Json).is_err());
+    }
+
+    #[test]
+    fn should_build_scenarios() {
+        let json = r#"{
+            "width": 10, "height": 10, "seed": 3,
+            "fish_density": 5, "shark_density": 2,
+            "placements": [{"kind": "shark", "x": 1, "y": 2}]
+        }"#;
+        let scenario = Scenario::parse(json, ScenarioFormat::Json).unwrap();
+
+        let sma = scenario.build().unwrap();
+        assert_eq!(sma.seed, 3);
+        assert_eq!(sma.agents.len(), 8);
+        assert_eq!(sma.agents[0].kind(), AgentKind::Shark);
+        assert_eq!(sma.agents[0].coordinate(), Point::new(1, 2));
+        assert_eq!(scenario.empty().unwrap().agents.len(), 0);
+
+        let replay = scenario.build().unwrap();
+        let coordinates = |sma: &crate::sma::Sma| {
+            sma.agents
+                .iter()
+                .map(|agent| agent.coordinate())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(coordinates(&replay), coordinates(&sma));
+    }
+
+    #[test]
+    fn should_build_energy_scenarios() {
+        let json = r#"{
+            "width": 10, "height": 10, "fish_density": 5, "shark_density": 2,
+            "rules": "energy", "energy": {"initial_energy": 9}
+        }"#;
+        let scenario = Scenario::parse(json, ScenarioFormat::Json).unwrap();
+        assert_eq!(scenario.rules, Rules::Energy);
+        assert_eq!(
+            scenario.energy.fish_energy,
+            EnergyRules::default().fish_energy
+        );
+
+        let sma = scenario.build().unwrap();
+        let sharks = sma
+            .agents
+            .iter()
+            .filter(|agent| agent.kind() == AgentKind::Shark);
+        assert!(sharks
+            .map(|shark| shark.energy())
+            .all(|energy| energy == Some(9)));
+    }
+
+    #[test]
+    fn should_build_resource_scenarios() {
+        let toml = r#"
+            width = 4
+            height = 4
+            fish_density = 3
+            shark_density = 0
+            rules = "energy"
+
+            [energy]
+            grass_energy = 2
+
+            [resources]
+            regrowth = "logistic:0.25"
+        "#;
+        let scenario = Scenario::parse(toml, ScenarioFormat::Toml).unwrap();
+        let resources = scenario.resources.as_ref().unwrap();
+        assert_eq!(resources.regrowth, Regrowth::Logistic(0.25));
+        assert_eq!(resources.capacity, ResourceRules::default().capacity);
+
+        let sma = scenario.build().unwrap();
+        assert_eq!(sma.env.resource(Point::new(3, 3)), Some(1.0));
+        assert!(sma.agents.iter().all(|fish| fish.energy().is_some()));
+        assert_eq!(
+            Scenario::default()
+                .build()
+                .unwrap()
+                .env
+                .resource(Point::new(0, 0)),
+            None
+        );
+    }
+}
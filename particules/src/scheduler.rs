@@ -0,0 +1,56 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Order in which agents decide and update during a tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scheduler {
+    /// Each agent decides then updates in turn, the order is shuffled after every tick.
+    #[default]
+    Shuffled,
+    /// Each agent decides then updates in turn, always in insertion order.
+    Sequential,
+    /// Every agent decides on the same environment, then every agent updates.
+    Synchronous,
+    /// As many random picks (with replacement) as there are agents, each deciding then updating.
+    RandomAsync,
+}
+
+impl Scheduler {
+    pub const ALL: [Scheduler; 4] = [
+        Scheduler::Shuffled,
+        Scheduler::Sequential,
+        Scheduler::Synchronous,
+        Scheduler::RandomAsync,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Scheduler::Shuffled => "shuffled",
+            Scheduler::Sequential => "sequential",
+            Scheduler::Synchronous => "synchronous",
+            Scheduler::RandomAsync => "random_async",
+        }
+    }
+}
+
+impl fmt::Display for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Scheduler {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Scheduler::ALL
+            .iter()
+            .find(|scheduler| scheduler.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownScheduler(value.into()))
+    }
+}
@@ -1,16 +1,34 @@
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::{Read, Write};
 
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use log::{debug, error, info, trace};
+use rand::{seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
+use crate::conflict::ConflictPolicy;
+use crate::core::Action;
 use crate::environment::Cell;
 use crate::environment::Environment;
+use crate::export::{ExportRow, Exporter};
+use crate::neighbourhood::Neighbourhood;
+use crate::observer::{self, Observer};
 use crate::particules::agent::Agent;
+use crate::resource::{self, ResourceLayer, ResourceRules};
+use crate::scheduler::Scheduler;
+use crate::snapshot::SnapshotFormat;
+use crate::stats::{Oscillation, TickStats, TickSummary};
+use crate::stop::{RunOutcome, StopCondition};
+use crate::topology::Topology;
+use crate::wator::energy::EnergyRules;
 use crate::wator::fish::Fish;
+use crate::wator::lifecycle::Lifecycle;
 use crate::wator::shark::Shark;
 use crate::AgentCommand;
+use crate::AgentId;
 use crate::AgentKind;
-use crate::SMA;
+use crate::Error;
+use crate::SmaRng;
 
 use super::particules::agent::Decision;
 use super::wator::fish::Decision as FishDecision;
@@ -21,123 +39,471 @@ use super::HDirection;
 use super::Point;
 use super::VDirection;
 
+/// Rgb colour a cell is drawn with.
+type Color = (f32, f32, f32);
+
+#[derive(Serialize, Deserialize)]
 pub struct Sma {
     pub env: Environment,
     pub agents: Vec<AgentRef>,
+    #[serde(skip)]
+    registry: HashMap<AgentId, AgentRef>,
+    /// Always empty between two ticks.
+    #[serde(skip)]
     pub(crate) next_generation: Vec<AgentRef>,
     pub turn: i32,
+    pub seed: u64,
+    pub scheduler: Scheduler,
+    pub conflict_policy: ConflictPolicy,
+    /// Fish and sharks are placed at a random point of their breeding cycle.
+    #[serde(default)]
+    pub random_age: bool,
+    stats: TickStats,
+    history: Vec<TickSummary>,
+    #[serde(default)]
+    oscillations: BTreeMap<AgentKind, Oscillation>,
+    #[serde(skip)]
+    debug: bool,
+    #[serde(skip)]
+    exporter: Option<Exporter>,
+    #[serde(skip)]
+    observers: Vec<Box<dyn Observer + Send>>,
+    /// Cells whose content changed during the last tick, in index order.
+    #[serde(skip)]
+    changed_cells: Vec<Point>,
+    /// Whether `changed_cells` is recorded, see `set_track_changes`.
+    #[serde(skip)]
+    track_changes: bool,
+    /// Look at the start of the tick of every cell altered since.
+    #[serde(skip)]
+    touched: Vec<(Point, Option<Color>)>,
+    /// Whether each cell, by index, is already in `touched`.
+    #[serde(skip)]
+    is_touched: Vec<bool>,
+    rng: SmaRng,
 }
 
 impl Sma {
     pub fn tick(&mut self) {
-        // Update all agent positions sequentialy
-        let env = &mut self.env;
-        println!("agent count {}", &self.agents.len());
+        debug!("tick {} start, {} agents", self.turn, self.agents.len());
+        self.stats = TickStats {
+            turn: self.turn,
+            ..TickStats::default()
+        };
+        let turn = self.turn;
+        observer::notify(&mut self.observers, |observer| observer.on_tick_start(turn));
+        // Cells eaten during a tick start growing back on the next one
+        let repainted = match &mut self.env.resources {
+            Some(layer) => layer.grow(),
+            None => vec![],
+        };
+        for (index, previous) in repainted {
+            let point = Point::new(index as i32 % self.env.width, index as i32 / self.env.width);
+            // Agents hide the resources of their cell
+            if self.env.is_empty(point) {
+                self.remember(point, Some(resource::tint(previous)));
+            }
+        }
 
-        for idx in 0..self.agents.len() {
-            &mut self.agents[idx].decide(env);
-            match self.agents[idx].update(env) {
-                AgentCommand::DoNothing => (),
-                AgentCommand::Create(agent) => self.next_generation.push(agent),
-            };
+        match self.scheduler {
+            Scheduler::Shuffled | Scheduler::Sequential => self.step_sequential(),
+            Scheduler::Synchronous => self.step_synchronous(),
+            Scheduler::RandomAsync => self.step_random_async(),
         }
 
-        &mut self
-            .agents
-            .iter()
-            .filter(|agent| *agent.marked_for_removal.lock().unwrap())
-            .for_each(|agent| env.set_cell(agent.coordinate(), Cell::Empty).unwrap());
+        while let Some(agent_ref) = self.next_generation.pop() {
+            let coordinate = agent_ref.coordinate();
+            let (kind, id) = (agent_ref.kind(), agent_ref.id());
+            trace!("{} {} born at {}:{}", kind, id, coordinate.x, coordinate.y);
+            self.stats.births.push(agent_ref.id());
+            observer::notify(&mut self.observers, |observer| {
+                observer.on_agent_born(&agent_ref)
+            });
+            self.registry.insert(agent_ref.id(), agent_ref.clone());
+            self.agents.push(agent_ref);
+        }
 
-        println!("env {}", self.get_state().len());
-        println!("size {}", self.agents.len());
-        &mut self
+        let dead: Vec<Point> = self
             .agents
             .iter()
-            .for_each(|agent| println!("remove ? {}", *agent.marked_for_removal.lock().unwrap()));
-        &mut self
-            .agents
-            .retain(|agent| !*agent.marked_for_removal.lock().unwrap());
-        println!("size after removal{}", self.agents.len());
+            .filter(|agent| agent.is_marked_for_removal())
+            .map(|agent| agent.coordinate())
+            .collect();
+        for coordinate in dead {
+            self.touch(coordinate);
+        }
+        let env = &mut self.env;
+        self.agents
+            .iter()
+            .filter(|agent| agent.is_marked_for_removal())
+            .for_each(|agent| {
+                let coordinate = agent.coordinate();
+                // An eaten agent's last known cell may already hold its predator
+                let still_there = match env.get_cell(coordinate) {
+                    Some(Cell::Filled(occupant)) => occupant.is_same(agent),
+                    _ => false,
+                };
+                if still_there {
+                    env.set_cell(coordinate, Cell::Empty).unwrap();
+                }
+            });
 
-        while let Some(agent_ref) = self.next_generation.pop() {
-            self.agents.push(agent_ref);
+        for agent in self.agents.iter() {
+            if let Some(cause) = agent.death_cause() {
+                let coordinate = agent.coordinate();
+                trace!(
+                    "{} {} died at {}:{} ({:?})",
+                    agent.kind(),
+                    agent.id(),
+                    coordinate.x,
+                    coordinate.y,
+                    cause
+                );
+                self.stats.record_death(agent.id(), cause);
+                observer::notify(&mut self.observers, |observer| {
+                    observer.on_agent_died(agent, cause)
+                });
+                self.registry.remove(&agent.id());
+            }
+        }
+        self.agents.retain(|agent| !agent.is_marked_for_removal());
+
+        if self.debug {
+            debug!("tick {} environment:\n{}", self.turn, self.env.debug());
         }
 
-        self.env.debug();
+        for agent in self.agents.iter() {
+            *self.stats.population.entry(agent.kind()).or_insert(0) += 1;
+        }
+        self.record_changed_cells();
+        debug!(
+            "tick {} end, {} agents, {} births, {} deaths",
+            self.turn,
+            self.agents.len(),
+            self.stats.births.len(),
+            self.stats.deaths.len()
+        );
+        for &kind in AgentKind::ALL.iter() {
+            let population = self.stats.population(kind);
+            self.oscillations
+                .entry(kind)
+                .or_default()
+                .record(population);
+        }
+        self.history.push(TickSummary::from(&self.stats));
+        let stats = &self.stats;
+        observer::notify(&mut self.observers, |observer| observer.on_tick_end(stats));
+        self.export();
 
-        self.shuffle_agents();
-        println!("turn {}", self.turn);
+        if let Scheduler::Shuffled = self.scheduler {
+            self.shuffle_agents();
+        }
         self.turn += 1;
     }
 
-    pub fn new(height: i32, width: i32) {
-        let env = Environment::new(height, width, false);
-        SMA.write().unwrap().env = env;
+    /// Colour of the agent on the cell, or the tint of its resources, `None` when plain empty.
+    fn look(&self, point: Point) -> Option<Color> {
+        match self.env.get_cell(point) {
+            Some(Cell::Filled(agent)) => Some(agent.get_color()),
+            _ => self.env.resource(point).map(resource::tint),
+        }
+    }
+
+    /// Remember how the cell looks before it is altered, only the first call of a tick counts.
+    fn touch(&mut self, point: Point) {
+        if self.track_changes && !self.is_touched(point) {
+            let look = self.look(point);
+            self.remember(point, look);
+        }
+    }
+
+    fn is_touched(&mut self, point: Point) -> bool {
+        let size = (self.env.width * self.env.height) as usize;
+        // Not saved in snapshots
+        if self.is_touched.len() != size {
+            self.is_touched = vec![false; size];
+        }
+        self.is_touched[self.env.get_index(point)]
+    }
+
+    fn remember(&mut self, point: Point, look: Option<Color>) {
+        if self.track_changes && !self.is_touched(point) {
+            let index = self.env.get_index(point);
+            self.is_touched[index] = true;
+            self.touched.push((point, look));
+        }
+    }
+
+    /// Only the cells touched during the tick are compared, not the whole grid.
+    fn record_changed_cells(&mut self) {
+        let mut touched = std::mem::take(&mut self.touched);
+        for (point, _) in touched.iter() {
+            let index = self.env.get_index(*point);
+            self.is_touched[index] = false;
+        }
+        touched.retain(|(point, before)| self.look(*point) != *before);
+        touched.sort_by_key(|(point, _)| self.env.get_index(*point));
+        self.changed_cells = touched.into_iter().map(|(point, _)| point).collect();
+    }
+
+    fn step_sequential(&mut self) {
+        for idx in 0..self.agents.len() {
+            self.step_agent(idx);
+        }
+    }
+
+    fn step_synchronous(&mut self) {
+        // Every decision is taken on the environment as it was at the start of the tick
+        for idx in 0..self.agents.len() {
+            if !self.agents[idx].is_marked_for_removal() {
+                self.agents[idx].decide(&self.env, &mut self.rng);
+            }
+        }
+
+        self.resolve_conflicts();
+
+        for idx in 0..self.agents.len() {
+            self.update_agent(idx, true);
+        }
+    }
+
+    fn resolve_conflicts(&mut self) {
+        // Keyed by cell index so contested cells are always visited in the same order
+        let mut contenders: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (idx, agent) in self.agents.iter().enumerate() {
+            if agent.is_marked_for_removal() {
+                continue;
+            }
+            if let Some(target) = agent.target(&self.env) {
+                contenders
+                    .entry(self.env.get_index(target))
+                    .or_default()
+                    .push(idx);
+            }
+        }
+
+        for (_, candidates) in contenders.into_iter().filter(|(_, c)| c.len() > 1) {
+            let winner = match self.conflict_policy {
+                ConflictPolicy::Random => Some(candidates[self.rng.gen_range(0, candidates.len())]),
+                ConflictPolicy::FirstCome => Some(candidates[0]),
+                ConflictPolicy::Oldest => candidates
+                    .iter()
+                    .cloned()
+                    .min_by_key(|idx| self.agents[*idx].id()),
+                ConflictPolicy::Hungriest => candidates
+                    .iter()
+                    .cloned()
+                    .min_by_key(|idx| self.agents[*idx].energy().unwrap_or(u32::MAX)),
+                ConflictPolicy::Stall => None,
+            };
+
+            candidates
+                .into_iter()
+                .filter(|idx| Some(*idx) != winner)
+                .for_each(|idx| self.agents[idx].stall());
+
+            self.stats.conflicts += 1;
+        }
+    }
+
+    fn step_random_async(&mut self) {
+        let count = self.agents.len();
+        for _ in 0..count {
+            let idx = self.rng.gen_range(0, count);
+            self.step_agent(idx);
+        }
+    }
+
+    fn step_agent(&mut self, idx: usize) {
+        if !self.agents[idx].is_marked_for_removal() {
+            self.agents[idx].decide(&self.env, &mut self.rng);
+            self.update_agent(idx, false);
+        }
+    }
+
+    fn update_agent(&mut self, idx: usize, synchronous: bool) {
+        if self.agents[idx].is_marked_for_removal() {
+            return;
+        }
+
+        let from = self.agents[idx].coordinate();
+        self.touch(from);
+        if let Some(reach) = self.agents[idx].reach(&self.env) {
+            self.touch(reach);
+        }
+        let command = if synchronous {
+            self.agents[idx].update_synchronous(&mut self.env)
+        } else {
+            self.agents[idx].update(&mut self.env)
+        };
+        match command {
+            AgentCommand::DoNothing => (),
+            AgentCommand::Create(agent) => self.next_generation.push(agent),
+        };
+
+        // Starving agents die during their own update and do not act
+        let agent = &self.agents[idx];
+        if !agent.is_marked_for_removal() {
+            let action = agent.last_action();
+            match action {
+                Action::Collided => {
+                    let coordinate = agent.coordinate();
+                    let (kind, id) = (agent.kind(), agent.id());
+                    trace!(
+                        "{} {} collided at {}:{}",
+                        kind,
+                        id,
+                        coordinate.x,
+                        coordinate.y
+                    );
+                    observer::notify(&mut self.observers, |observer| observer.on_collision(agent));
+                }
+                Action::Moved => {
+                    let to = agent.coordinate();
+                    observer::notify(&mut self.observers, |observer| {
+                        observer.on_agent_moved(agent, from, to)
+                    });
+                }
+                Action::Stalled | Action::Bounced => (),
+            }
+            self.stats.record_action(action);
+        }
+    }
+
+    pub fn new(width: i32, height: i32, seed: u64) -> Result<Sma, Error> {
+        let env = Environment::new(width, height, Topology::default())?;
+        Ok(Sma::with_env(env, seed))
     }
 
     pub fn new_with_fish(
-        height: i32,
         width: i32,
+        height: i32,
         breed_rime_fish: u8,
         breed_time_shark: u8,
         shark_starve_time: u8,
-    ) {
+        seed: u64,
+    ) -> Result<Sma, Error> {
         let env = Environment::new_fish_shark(
-            height,
             width,
-            false,
+            height,
+            Topology::default(),
             breed_rime_fish,
             breed_time_shark,
             shark_starve_time,
-        );
-        SMA.write().unwrap().env = env;
+        )?;
+        Ok(Sma::with_env(env, seed))
+    }
+
+    /// Write the whole simulation, including its random number generator state.
+    pub fn save<W: Write>(&self, writer: W, format: SnapshotFormat) -> Result<(), Error> {
+        match format {
+            SnapshotFormat::Json => {
+                serde_json::to_writer(writer, self).map_err(|err| Error::Snapshot(err.to_string()))
+            }
+            SnapshotFormat::Binary => bincode::serialize_into(writer, self)
+                .map_err(|err| Error::Snapshot(err.to_string())),
+        }
+    }
+
+    /// Read a simulation written by `save`, it resumes exactly where it was saved.
+    pub fn load<R: Read>(reader: R, format: SnapshotFormat) -> Result<Sma, Error> {
+        let mut sma: Sma =
+            match format {
+                SnapshotFormat::Json => serde_json::from_reader(reader)
+                    .map_err(|err| Error::Snapshot(err.to_string()))?,
+                SnapshotFormat::Binary => bincode::deserialize_from(reader)
+                    .map_err(|err| Error::Snapshot(err.to_string()))?,
+            };
+
+        let (width, height) = (sma.env.width, sma.env.height);
+        Environment::check_dimensions(width, height)?;
+        sma.env.cells = vec![Cell::Empty; (width * height) as usize];
+        for agent in sma.agents.iter() {
+            let coordinate = agent.coordinate();
+            match sma.env.get_cell(coordinate) {
+                Some(Cell::Empty) => sma.env.set_cell(coordinate, Cell::Filled(agent.clone()))?,
+                Some(Cell::Filled(_)) => return Err(Error::CellOccupied(coordinate)),
+                None => return Err(Error::OutOfBounds(coordinate)),
+            }
+            sma.registry.insert(agent.id(), agent.clone());
+        }
+        Ok(sma)
+    }
+
+    fn with_env(env: Environment, seed: u64) -> Sma {
+        Sma {
+            env,
+            agents: vec![],
+            registry: HashMap::new(),
+            next_generation: vec![],
+            turn: 0,
+            seed,
+            scheduler: Scheduler::default(),
+            conflict_policy: ConflictPolicy::default(),
+            random_age: false,
+            stats: TickStats::default(),
+            history: vec![],
+            oscillations: BTreeMap::new(),
+            debug: false,
+            exporter: None,
+            observers: vec![],
+            changed_cells: vec![],
+            track_changes: false,
+            touched: vec![],
+            is_touched: vec![],
+            rng: SmaRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Heading drawn from the simulation generator, never standing still.
+    pub fn random_direction(&mut self) -> Direction {
+        Sma::pick_direction(&mut self.rng)
+    }
+
+    /// Draw a fresh seed for callers that do not need a reproducible run.
+    pub fn random_seed() -> u64 {
+        thread_rng().gen()
     }
 
     fn shuffle_agents(&mut self) {
         // Randomize agents order each turn
-        let mut agents = &mut self.agents;
-        let slice: &mut [AgentRef] = &mut agents;
-        slice.shuffle(&mut thread_rng());
-
-        self.agents = slice.into();
+        self.agents.shuffle(&mut self.rng);
     }
 
     pub fn add_agent(&mut self, agent: AgentRef) {
         self.next_generation.push(agent);
     }
 
-    pub fn gen_agent(&mut self, coordinate: Point, direction: Direction) -> Result<(), &str> {
-        let filled = self
-            .agents
-            .iter()
-            .find(|agent_in_memory| (agent_in_memory.coordinate()) == coordinate);
-
-        if let None = filled {
-            let agent = Agent {
-                collision: false,
-                coordinate,
-                previous_coordinate: coordinate,
-                direction,
-                decision: Decision::KeepCourse,
-            };
+    pub fn gen_agent(&mut self, coordinate: Point, direction: Direction) -> Result<AgentId, Error> {
+        let coordinate = self.free_cell(coordinate)?;
+        Ok(self.add_agent_unsafe(coordinate, direction))
+    }
 
-            let agent_ref = AgentRef {
-                inner: Arc::new(Mutex::new(Box::new(agent))),
-                marked_for_removal: Arc::new(Mutex::new(false)),
-                kind: AgentKind::Other,
-            };
+    /// Place a fish on an empty cell, its breed timer starts full.
+    pub fn gen_fish(&mut self, coordinate: Point) -> Result<AgentId, Error> {
+        let coordinate = self.free_cell(coordinate)?;
+        Ok(self.add_fish_unsafe(coordinate))
+    }
 
-            self.agents.push(agent_ref.clone());
-            self.env
-                .set_cell(coordinate, Cell::Filled(agent_ref))
-                .unwrap();
-            Ok(())
-        } else {
-            Err("Agent already stored at this location!")
+    /// Place a well fed shark on an empty cell.
+    pub fn gen_shark(&mut self, coordinate: Point) -> Result<AgentId, Error> {
+        let coordinate = self.free_cell(coordinate)?;
+        Ok(self.add_shark_unsafe(coordinate))
+    }
+
+    /// Normalized `coordinate`, provided the cell exists and is empty.
+    fn free_cell(&self, coordinate: Point) -> Result<Point, Error> {
+        let coordinate = match self.env.normalize(coordinate) {
+            Some(coordinate) => coordinate,
+            None => return Err(Error::OutOfBounds(coordinate)),
+        };
+        match self.env.get_cell(coordinate) {
+            Some(Cell::Empty) => Ok(coordinate),
+            _ => Err(Error::CellOccupied(coordinate)),
         }
     }
 
-    fn add_agent_unsafe(&mut self, coordinate: Point, direction: Direction) {
+    fn add_agent_unsafe(&mut self, coordinate: Point, direction: Direction) -> AgentId {
         let agent = Agent {
             collision: false,
             coordinate,
@@ -146,77 +512,90 @@ impl Sma {
             decision: Decision::KeepCourse,
         };
 
-        let agent_ref = AgentRef {
-            inner: Arc::new(Mutex::new(Box::new(agent))),
-            marked_for_removal: Arc::new(Mutex::new(false)),
-            kind: AgentKind::Other,
-        };
+        let agent_ref = AgentRef::from(agent, self.env.new_agent_id());
+        let id = agent_ref.id();
+        self.insert(agent_ref);
+        id
+    }
 
-        self.agents.push(agent_ref.clone());
-        self.env
-            .set_cell(coordinate, Cell::Filled(agent_ref))
-            .unwrap();
+    /// `lifecycle` of a newly placed agent, aged below `breed_time` if `random_age` is set.
+    fn initial_lifecycle(&mut self, lifecycle: Lifecycle, breed_time: u8) -> Lifecycle {
+        if self.random_age && breed_time > 0 {
+            lifecycle.aged(self.rng.gen_range(0, breed_time))
+        } else {
+            lifecycle
+        }
     }
 
-    fn add_fish_unsafe(&mut self, coordinate: Point) {
+    fn add_fish_unsafe(&mut self, coordinate: Point) -> AgentId {
+        let lifecycle = Fish::newborn_lifecycle(&self.env);
         let fish = Fish {
             coordinate,
             decision: FishDecision::Stall,
-            breed_count_down: self.env.shark_breed_time,
-        };
-
-        let fish_ref = AgentRef {
-            inner: Arc::new(Mutex::new(Box::new(fish))),
-            marked_for_removal: Arc::new(Mutex::new(false)),
-            kind: AgentKind::Fish,
+            lifecycle: self.initial_lifecycle(lifecycle, self.env.fish_breed_time),
         };
 
-        self.agents.push(fish_ref.clone());
-        self.env
-            .set_cell(coordinate, Cell::Filled(fish_ref))
-            .unwrap();
+        let fish_ref = AgentRef::from_fish(fish, self.env.new_agent_id());
+        let id = fish_ref.id();
+        self.insert(fish_ref);
+        id
     }
 
-    fn add_shark_unsafe(&mut self, coordinate: Point) {
+    fn add_shark_unsafe(&mut self, coordinate: Point) -> AgentId {
+        let lifecycle = match &self.env.energy_rules {
+            Some(rules) => Lifecycle::energetic(rules.initial_energy),
+            None => Lifecycle::shark(self.env.shark_breed_time, self.env.shark_starve_time),
+        };
         let shark = Shark {
             coordinate,
             decision: SharkDecision::Stall,
-            breed_count_down: self.env.shark_breed_time,
-            starve_time: self.env.shark_starve_time,
+            lifecycle: self.initial_lifecycle(lifecycle, self.env.shark_breed_time),
         };
 
-        let shark_ref = AgentRef {
-            inner: Arc::new(Mutex::new(Box::new(shark))),
-            marked_for_removal: Arc::new(Mutex::new(false)),
-            kind: AgentKind::Other,
-        };
+        let shark_ref = AgentRef::from_shark(shark, self.env.new_agent_id());
+        let id = shark_ref.id();
+        self.insert(shark_ref);
+        id
+    }
 
-        self.agents.push(shark_ref.clone());
+    fn insert(&mut self, agent_ref: AgentRef) {
+        self.registry.insert(agent_ref.id(), agent_ref.clone());
+        self.agents.push(agent_ref.clone());
         self.env
-            .set_cell(coordinate, Cell::Filled(shark_ref))
+            .set_cell(agent_ref.coordinate(), Cell::Filled(agent_ref))
             .unwrap();
     }
 
-    pub fn gen_agents(&mut self, density: u8) {
+    /// Look up a living agent by its identifier.
+    pub fn agent(&self, id: AgentId) -> Option<&AgentRef> {
+        self.registry.get(&id)
+    }
+
+    pub fn gen_agents(&mut self, density: u8) -> Result<(), Error> {
         if density > 100 {
-            panic!("Density must be inferior or equal to 100");
+            return Err(Error::InvalidDensity(density));
         }
         let size = self.env.height * self.env.width;
 
         let agent_count = (size as f32 / 100 as f32) * density as f32;
         let agent_count = agent_count as i32;
 
-        let mut vec: Vec<i32> = (0..size).collect();
-        let mut rng = thread_rng();
-        vec.shuffle(&mut rng);
+        let mut vec = self.empty_cells();
+        if agent_count as usize > vec.len() {
+            return Err(Error::PopulationExceedsCapacity {
+                requested: agent_count as usize,
+                capacity: vec.len(),
+            });
+        }
+        vec.shuffle(&mut self.rng);
 
-        println!(
-            " about to generate {} agents in a env of size {}",
+        info!(
+            "generating {} agents in an environment of {} cells",
             agent_count, size
         );
 
         (0..(agent_count as usize)).for_each(|i| {
-            let direction = Sma::pick_direction();
+            let direction = Sma::pick_direction(&mut self.rng);
             let idx = vec[i];
 
             let x = idx % self.env.width;
@@ -224,18 +603,25 @@ impl Sma {
             let point = Point { x, y };
 
             self.add_agent_unsafe(point, direction);
-        })
+        });
+        Ok(())
     }
 
-    pub fn gen_fish_agents(&mut self, fish_density: u8, shark_density: u8) {
+    pub fn gen_fish_agents(&mut self, fish_density: u8, shark_density: u8) -> Result<(), Error> {
         let size = self.env.height * self.env.width;
 
-        let mut vec: Vec<i32> = (0..size).collect();
-        let mut rng = thread_rng();
-        vec.shuffle(&mut rng);
+        let mut vec = self.empty_cells();
+        let requested = fish_density as usize + shark_density as usize;
+        if requested > vec.len() {
+            return Err(Error::PopulationExceedsCapacity {
+                requested,
+                capacity: vec.len(),
+            });
+        }
+        vec.shuffle(&mut self.rng);
 
-        println!(
-            " about to generate {} fish and {} sharks in a env of size {}",
+        info!(
+            "generating {} fish and {} sharks in an environment of {} cells",
             fish_density, shark_density, size
         );
         (0..(fish_density as usize)).for_each(|_| {
@@ -257,11 +643,17 @@ impl Sma {
 
             self.add_shark_unsafe(point);
         });
+        Ok(())
     }
 
-    fn pick_direction() -> Direction {
-        let mut rng = thread_rng();
+    fn empty_cells(&self) -> Vec<i32> {
+        let size = self.env.height * self.env.width;
+        (0..size)
+            .filter(|idx| self.env.cells[*idx as usize].is_empty_cell())
+            .collect()
+    }
 
+    fn pick_direction(rng: &mut SmaRng) -> Direction {
         loop {
             let x_dir = match rng.gen_range(0, 3) {
                 0 => HDirection::Right,
@@ -293,7 +685,988 @@ impl Sma {
         self.env.get_index(point)
     }
 
-    pub fn set_borderless(&mut self, value: bool) {
-        self.env.borderless = value;
+    /// Dump the whole environment in the debug log after every tick, costly on large worlds.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.env.topology = topology;
+    }
+
+    /// Cells Wa-Tor agents look at when choosing where to go.
+    pub fn set_neighbourhood(&mut self, neighbourhood: Neighbourhood) {
+        self.env.neighbourhood = neighbourhood;
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = scheduler;
+    }
+
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// Make sharks live on energy rather than count-downs, `None` restores the classic rules.
+    /// Only affects the sharks placed from now on.
+    pub fn set_energy_rules(&mut self, rules: Option<EnergyRules>) {
+        self.env.energy_rules = rules;
+    }
+
+    /// Grow resources on every cell for the fish to eat, `None` removes them.
+    pub fn set_resources(&mut self, rules: Option<ResourceRules>) {
+        let size = (self.env.width * self.env.height) as usize;
+        self.env.resources = rules.map(|rules| ResourceLayer::new(size, rules));
+    }
+
+    /// Record the cells changed by every tick from now on, headless runs leave it off.
+    pub fn set_track_changes(&mut self, track_changes: bool) {
+        self.track_changes = track_changes;
+        self.changed_cells.clear();
+    }
+
+    /// Spread the first births of the fish and sharks placed from now on.
+    pub fn set_random_age(&mut self, random_age: bool) {
+        self.random_age = random_age;
+    }
+
+    /// Statistics of the last tick.
+    pub fn stats(&self) -> &TickStats {
+        &self.stats
+    }
+
+    /// Counters of every tick run so far, oldest first.
+    pub fn history(&self) -> &[TickSummary] {
+        &self.history
+    }
+
+    /// Oscillation of the population of `kind` so far, `None` before the first tick.
+    pub fn oscillation(&self, kind: AgentKind) -> Option<&Oscillation> {
+        self.oscillations.get(&kind)
+    }
+
+    /// Write a row to `exporter` at the end of every tick, replacing any previous exporter.
+    pub fn attach_exporter(&mut self, exporter: Exporter) {
+        self.exporter = Some(exporter);
+    }
+
+    pub fn detach_exporter(&mut self) -> Option<Exporter> {
+        self.exporter.take()
+    }
+
+    /// Cells that gained, lost or changed the colour of their agent during the last tick,
+    /// renderers only need to repaint those. Always empty unless `set_track_changes` is on.
+    pub fn changed_cells(&self) -> &[Point] {
+        &self.changed_cells
+    }
+
+    /// Subscribe `observer` to the events of every following tick.
+    pub fn add_observer<O: Observer + Send + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// Tick until one of `conditions` holds, they are checked before every tick.
+    /// Without any condition that can hold, this never returns.
+    pub fn run_until(&mut self, conditions: &[StopCondition]) -> RunOutcome {
+        loop {
+            if let Some(outcome) = self.stop_reason(conditions) {
+                info!("run stopped at turn {}: {}", self.turn, outcome);
+                return outcome;
+            }
+            self.tick();
+        }
+    }
+
+    /// Outcome of the first of `conditions` holding at this point of the run.
+    pub fn stop_reason(&self, conditions: &[StopCondition]) -> Option<RunOutcome> {
+        conditions
+            .iter()
+            .find_map(|condition| condition.check(self))
+    }
+
+    fn export(&mut self) {
+        let row = match self.exporter {
+            Some(_) => ExportRow::from_sma(self),
+            None => return,
+        };
+        if let Some(exporter) = self.exporter.as_mut() {
+            // A failing export must not stop the simulation, it is dropped instead
+            if let Err(err) = exporter.write(&row) {
+                error!("{}, statistics export stopped", err);
+                self.exporter = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::conflict::ConflictPolicy;
+    use crate::environment::Cell;
+    use crate::observer::Observer;
+    use crate::resource::{Regrowth, ResourceRules};
+    use crate::scheduler::Scheduler;
+    use crate::sma::Sma;
+    use crate::snapshot::SnapshotFormat;
+    use crate::stats::{TickStats, TickSummary};
+    use crate::stop::{RunOutcome, StopCondition};
+    use crate::topology::Topology;
+    use crate::wator::energy::EnergyRules;
+    use crate::wator::lifecycle::Lifecycle;
+    use crate::wator::shark::Decision;
+    use crate::wator::shark::Shark;
+    use crate::AgentKind;
+    use crate::AgentRef;
+    use crate::DeathCause;
+    use crate::Direction;
+    use crate::Error;
+    use crate::HDirection;
+    use crate::Point;
+    use crate::VDirection;
+
+    fn state(sma: &Sma) -> Vec<Option<Point>> {
+        sma.get_state()
+            .iter()
+            .map(|cell| match cell {
+                Cell::Empty => None,
+                Cell::Filled(agent) => Some(agent.coordinate()),
+            })
+            .collect()
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn record(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Observer for Recorder {
+        fn on_tick_start(&mut self, turn: i32) {
+            self.record(format!("start {}", turn));
+        }
+
+        fn on_agent_born(&mut self, agent: &AgentRef) {
+            self.record(format!("born {}", agent.kind()));
+        }
+
+        fn on_agent_died(&mut self, agent: &AgentRef, cause: DeathCause) {
+            self.record(format!("died {} {:?}", agent.kind(), cause));
+        }
+
+        fn on_agent_moved(&mut self, agent: &AgentRef, from: Point, to: Point) {
+            let (kind, x, y) = (agent.kind(), to.x - from.x, to.y - from.y);
+            self.record(format!("moved {} {}:{}", kind, x, y));
+        }
+
+        fn on_collision(&mut self, agent: &AgentRef) {
+            self.record(format!("collision {}", agent.id()));
+        }
+
+        fn on_tick_end(&mut self, stats: &TickStats) {
+            self.record(format!("end {}", stats.turn));
+        }
+    }
+
+    fn run(mut sma: Sma, ticks: usize) -> Vec<Vec<Option<Point>>> {
+        (0..ticks)
+            .map(|_| {
+                sma.tick();
+                state(&sma)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_run_independent_simulations() {
+        let mut first = Sma::new_with_fish(5, 5, 3, 3, 3, 0).unwrap();
+        let second = Sma::new(10, 10, 0).unwrap();
+
+        first.gen_fish_agents(4, 0).unwrap();
+        first.tick();
+
+        assert_eq!(first.get_state().len(), 25);
+        assert_eq!(second.get_state().len(), 100);
+        assert!(second.agents.is_empty());
+        assert_eq!(first.turn, 1);
+        assert_eq!(second.turn, 0);
+    }
+
+    #[test]
+    fn should_replay_fish_run_with_same_seed() {
+        let mut first = Sma::new_with_fish(10, 10, 3, 3, 3, 42).unwrap();
+        let mut second = Sma::new_with_fish(10, 10, 3, 3, 3, 42).unwrap();
+        first.gen_fish_agents(20, 0).unwrap();
+        second.gen_fish_agents(20, 0).unwrap();
+
+        assert_eq!(state(&first), state(&second));
+        assert_eq!(run(first, 20), run(second, 20));
+    }
+
+    #[test]
+    fn should_replay_particules_run_with_same_seed() {
+        let mut first = Sma::new(10, 10, 7).unwrap();
+        let mut second = Sma::new(10, 10, 7).unwrap();
+        first.gen_agents(30).unwrap();
+        second.gen_agents(30).unwrap();
+
+        assert_eq!(state(&first), state(&second));
+        assert_eq!(run(first, 20), run(second, 20));
+    }
+
+    #[test]
+    fn should_keep_environment_consistent_with_every_scheduler() {
+        for scheduler in Scheduler::ALL.iter() {
+            let mut sma = Sma::new_with_fish(10, 10, 3, 3, 4, 2).unwrap();
+            sma.set_scheduler(*scheduler);
+            sma.gen_fish_agents(30, 5).unwrap();
+
+            for _ in 0..30 {
+                sma.tick();
+
+                let filled = sma.get_state().iter().filter(|cell| !cell.is_empty_cell());
+                assert_eq!(filled.count(), sma.agents.len(), "{}", scheduler);
+                sma.agents
+                    .iter()
+                    .for_each(|agent| match sma.env.get_cell(agent.coordinate()) {
+                        Some(Cell::Filled(occupant)) => {
+                            assert!(occupant.is_same(agent), "{}", scheduler)
+                        }
+                        _ => panic!("{}: agent missing from its cell", scheduler),
+                    });
+            }
+        }
+    }
+
+    #[test]
+    fn should_separate_head_on_particules_with_every_scheduler() {
+        let left = Direction::new(HDirection::Left, VDirection::None);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        for scheduler in Scheduler::ALL.iter() {
+            let mut sma = Sma::new(10, 1, 0).unwrap();
+            sma.set_scheduler(*scheduler);
+            let first = sma.gen_agent(Point::new(4, 0), right).unwrap();
+            let second = sma.gen_agent(Point::new(5, 0), left).unwrap();
+
+            sma.tick();
+            assert_eq!(
+                sma.agent(first).unwrap().direction(),
+                Some(left),
+                "{}",
+                scheduler
+            );
+            assert_eq!(
+                sma.agent(second).unwrap().direction(),
+                Some(right),
+                "{}",
+                scheduler
+            );
+
+            sma.tick();
+            sma.tick();
+            let x = |id| sma.agent(id).unwrap().coordinate().x;
+            let gap = x(second) - x(first);
+            assert!(gap > 1, "{}: stuck {} cells apart", scheduler, gap);
+        }
+    }
+
+    fn contested_fish(policy: ConflictPolicy) -> Sma {
+        // Both fish can only move into the middle cell
+        let mut sma = Sma::new_with_fish(3, 1, 10, 10, 10, 0).unwrap();
+        sma.set_scheduler(Scheduler::Synchronous);
+        sma.set_conflict_policy(policy);
+        sma.add_fish_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(2, 0));
+        sma.tick();
+        sma
+    }
+
+    #[test]
+    fn should_let_one_contender_move() {
+        for policy in &[ConflictPolicy::Random, ConflictPolicy::FirstCome] {
+            let sma = contested_fish(*policy);
+
+            assert_eq!(sma.stats().conflicts, 1);
+            assert!(!sma.env.is_empty(Point::new(1, 0)));
+            assert_eq!(
+                sma.env.is_empty(Point::new(0, 0)),
+                !sma.env.is_empty(Point::new(2, 0))
+            );
+        }
+    }
+
+    #[test]
+    fn should_stall_every_contender() {
+        let sma = contested_fish(ConflictPolicy::Stall);
+
+        assert_eq!(sma.stats().conflicts, 1);
+        assert!(sma.env.is_empty(Point::new(1, 0)));
+        assert!(!sma.env.is_empty(Point::new(0, 0)));
+        assert!(!sma.env.is_empty(Point::new(2, 0)));
+    }
+
+    #[test]
+    fn should_feed_hungriest_shark() {
+        let mut sma = Sma::new_with_fish(3, 1, 10, 10, 10, 0).unwrap();
+        sma.set_scheduler(Scheduler::Synchronous);
+        sma.set_conflict_policy(ConflictPolicy::Hungriest);
+        sma.add_fish_unsafe(Point::new(1, 0));
+
+        let sated = Shark {
+            coordinate: Point::new(0, 0),
+            decision: Decision::Stall,
+            lifecycle: Lifecycle::shark(10, 8),
+        };
+        let hungry = Shark {
+            coordinate: Point::new(2, 0),
+            decision: Decision::Stall,
+            lifecycle: Lifecycle::shark(10, 3),
+        };
+        let sated = AgentRef::from_shark(sated, sma.env.new_agent_id());
+        let hungry = AgentRef::from_shark(hungry, sma.env.new_agent_id());
+        sma.insert(sated.clone());
+        sma.insert(hungry.clone());
+
+        sma.tick();
+
+        assert_eq!(sma.stats().conflicts, 1);
+        assert_eq!(hungry.coordinate(), Point::new(1, 0));
+        assert_eq!(sated.coordinate(), Point::new(0, 0));
+        assert_eq!(sma.agents.len(), 2);
+    }
+
+    #[test]
+    fn should_track_agents_by_id() {
+        let mut sma = Sma::new_with_fish(2, 1, 1, 0, 10, 0).unwrap();
+        sma.add_fish_unsafe(Point::new(0, 0));
+        let parent = sma.agents[0].id();
+
+        sma.tick();
+
+        let child = sma.stats().births[0];
+        assert!(child > parent);
+        assert_eq!(sma.agent(parent).unwrap().coordinate(), Point::new(1, 0));
+        assert_eq!(sma.agent(child).unwrap().coordinate(), Point::new(0, 0));
+    }
+
+    #[test]
+    fn should_place_fish_and_sharks() {
+        let mut sma = Sma::new_with_fish(3, 3, 3, 3, 3, 0).unwrap();
+        let fish = sma.gen_fish(Point::new(1, 1)).unwrap();
+        let shark = sma.gen_shark(Point::new(2, 1)).unwrap();
+
+        assert_eq!(sma.agent(fish).unwrap().kind(), AgentKind::Fish);
+        assert_eq!(sma.agent(shark).unwrap().kind(), AgentKind::Shark);
+        assert_eq!(
+            sma.gen_shark(Point::new(1, 1)),
+            Err(Error::CellOccupied(Point::new(1, 1)))
+        );
+        assert_eq!(
+            sma.gen_fish(Point::new(3, 0)),
+            Err(Error::OutOfBounds(Point::new(3, 0)))
+        );
+    }
+
+    #[test]
+    fn should_forget_eaten_fish() {
+        let mut sma = Sma::new_with_fish(2, 1, 10, 10, 10, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(1, 0));
+        let fish = sma.agents[1].id();
+
+        sma.tick();
+
+        assert_eq!(sma.stats().deaths, vec![fish]);
+        assert_eq!(sma.stats().eaten, 1);
+        assert!(sma.agent(fish).is_none());
+        assert_eq!(sma.agents.len(), 1);
+    }
+
+    #[test]
+    fn should_notify_observers() {
+        let recorder = Recorder::default();
+        let mut sma = Sma::new_with_fish(2, 1, 1, 10, 10, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(1, 0));
+        sma.set_scheduler(Scheduler::Sequential);
+        sma.add_observer(recorder.clone());
+
+        sma.tick();
+        sma.clear_observers();
+        sma.tick();
+
+        let expected = vec!["start 0", "moved shark 1:0", "died fish Eaten", "end 0"];
+        assert_eq!(recorder.events(), expected);
+
+        let recorder = Recorder::default();
+        let mut sma = Sma::new(5, 5, 0).unwrap();
+        sma.set_scheduler(Scheduler::Sequential);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        let left = Direction::new(HDirection::Left, VDirection::None);
+        let first = sma.gen_agent(Point::new(1, 2), right).unwrap();
+        sma.gen_agent(Point::new(2, 2), left).unwrap();
+        sma.add_observer(recorder.clone());
+
+        sma.tick();
+
+        let collision = format!("collision {}", first);
+        let expected = vec!["start 0", &collision, "moved particule 1:0", "end 0"];
+        assert_eq!(recorder.events(), expected);
+    }
+
+    #[test]
+    fn should_report_changed_cells() {
+        let mut sma = Sma::new(5, 5, 0).unwrap();
+        sma.set_track_changes(true);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        sma.gen_agent(Point::new(1, 2), right).unwrap();
+        sma.gen_agent(
+            Point::new(0, 0),
+            Direction::new(HDirection::Left, VDirection::None),
+        )
+        .unwrap();
+        assert!(sma.changed_cells().is_empty());
+
+        sma.tick();
+        // The agent bouncing off the wall stays put but turns red
+        let expected = [Point::new(0, 0), Point::new(1, 2), Point::new(2, 2)];
+        assert_eq!(sma.changed_cells(), &expected);
+
+        sma.tick();
+        let expected = [
+            Point::new(0, 0),
+            Point::new(1, 0),
+            Point::new(2, 2),
+            Point::new(3, 2),
+        ];
+        assert_eq!(sma.changed_cells(), &expected);
+
+        let mut sma = Sma::new_with_fish(3, 1, 10, 10, 10, 0).unwrap();
+        sma.set_track_changes(true);
+        sma.add_shark_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(1, 0));
+
+        sma.tick();
+        assert_eq!(sma.changed_cells(), &[Point::new(0, 0), Point::new(1, 0)]);
+
+        // Headless runs do not pay for it
+        sma.set_track_changes(false);
+        sma.tick();
+        assert!(sma.changed_cells().is_empty());
+    }
+
+    #[test]
+    fn should_report_the_same_cells_as_a_full_grid_diff() {
+        let looks = |sma: &Sma| {
+            let points = (0..8).flat_map(|y| (0..8).map(move |x| Point::new(x, y)));
+            points
+                .map(|point| (point, sma.look(point)))
+                .collect::<Vec<_>>()
+        };
+        for &scheduler in Scheduler::ALL.iter() {
+            let mut particules = Sma::new(8, 8, 3).unwrap();
+            particules.gen_agents(40).unwrap();
+            let mut wator = Sma::new_with_fish(8, 8, 2, 4, 3, 3).unwrap();
+            wator.set_resources(Some(ResourceRules {
+                regrowth: Regrowth::Logistic(0.3),
+                ..ResourceRules::default()
+            }));
+            wator.gen_fish_agents(20, 4).unwrap();
+
+            for sma in [&mut particules, &mut wator].iter_mut() {
+                sma.set_scheduler(scheduler);
+                sma.set_track_changes(true);
+                for _ in 0..20 {
+                    let before = looks(sma);
+                    sma.tick();
+                    let expected: Vec<Point> = before
+                        .into_iter()
+                        .zip(looks(sma))
+                        .filter(|((_, before), (_, after))| before != after)
+                        .map(|((point, _), _)| point)
+                        .collect();
+                    assert_eq!(sma.changed_cells(), &expected[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_report_invalid_setups() {
+        assert_eq!(
+            Sma::new(0, 5, 0).err(),
+            Some(Error::InvalidDimensions {
+                width: 0,
+                height: 5
+            })
+        );
+
+        let mut sma = Sma::new_with_fish(2, 2, 3, 3, 3, 0).unwrap();
+        assert_eq!(sma.gen_agents(101), Err(Error::InvalidDensity(101)));
+        assert_eq!(
+            sma.gen_fish_agents(3, 2),
+            Err(Error::PopulationExceedsCapacity {
+                requested: 5,
+                capacity: 4
+            })
+        );
+        assert!(sma.agents.is_empty());
+
+        let direction = Direction::new(HDirection::Right, VDirection::None);
+        let id = sma.gen_agent(Point::new(1, 1), direction).unwrap();
+        assert_eq!(sma.agent(id).unwrap().coordinate(), Point::new(1, 1));
+        assert_eq!(
+            sma.gen_agent(Point::new(1, 1), direction),
+            Err(Error::CellOccupied(Point::new(1, 1)))
+        );
+        assert_eq!(
+            sma.gen_agent(Point::new(2, 0), direction),
+            Err(Error::OutOfBounds(Point::new(2, 0)))
+        );
+    }
+
+    #[test]
+    fn should_expose_agent_capabilities() {
+        let mut sma = Sma::new_with_fish(3, 1, 4, 5, 6, 0).unwrap();
+        let direction = Direction::new(HDirection::Right, VDirection::None);
+        let particule = sma.gen_agent(Point::new(0, 0), direction).unwrap();
+        sma.add_fish_unsafe(Point::new(1, 0));
+        sma.add_shark_unsafe(Point::new(2, 0));
+
+        let particule = sma.agent(particule).unwrap();
+        assert_eq!(particule.direction(), Some(direction));
+        assert_eq!(particule.collision(), Some(false));
+        assert_eq!(particule.energy(), None);
+
+        let fish = &sma.agents[1];
+        assert_eq!(fish.direction(), None);
+        assert_eq!(fish.collision(), None);
+        assert!(fish.breed_count_down().is_some());
+
+        let shark = &sma.agents[2];
+        assert_eq!(shark.energy(), Some(6));
+        assert_eq!(shark.breed_count_down(), Some(5));
+    }
+
+    #[test]
+    fn should_move_across_wrapped_edges() {
+        let mut torus = Sma::new(5, 5, 0).unwrap();
+        torus.set_topology(Topology::Torus);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        let id = torus.gen_agent(Point::new(4, 2), right).unwrap();
+        torus.tick();
+
+        let agent = torus.agent(id).unwrap();
+        assert_eq!(agent.coordinate(), Point::new(0, 2));
+        assert_eq!(agent.collision(), Some(false));
+        assert!(torus.env.is_empty(Point::new(4, 2)));
+
+        let mut cylinder = Sma::new(5, 5, 0).unwrap();
+        cylinder.set_topology(Topology::HorizontalCylinder);
+        let down = Direction::new(HDirection::None, VDirection::Down);
+        let id = cylinder.gen_agent(Point::new(2, 4), down).unwrap();
+        cylinder.tick();
+
+        let agent = cylinder.agent(id).unwrap();
+        assert_eq!(agent.coordinate(), Point::new(2, 4));
+        assert_eq!(agent.direction().unwrap().y, VDirection::Up);
+        assert_eq!(agent.collision(), Some(true));
+    }
+
+    #[test]
+    fn should_collide_across_wrapped_edges() {
+        let mut sma = Sma::new(5, 5, 0).unwrap();
+        sma.set_topology(Topology::Torus);
+        sma.set_scheduler(Scheduler::Sequential);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        let left = Direction::new(HDirection::Left, VDirection::None);
+        let first = sma.gen_agent(Point::new(4, 2), right).unwrap();
+        let second = sma.gen_agent(Point::new(0, 2), left).unwrap();
+
+        sma.tick();
+
+        let first = sma.agent(first).unwrap();
+        assert_eq!(first.coordinate(), Point::new(4, 2));
+        assert_eq!(first.direction(), Some(left));
+        assert_eq!(first.collision(), Some(true));
+        assert_eq!(sma.agent(second).unwrap().direction(), Some(right));
+    }
+
+    #[test]
+    fn should_keep_environment_consistent_with_every_topology() {
+        for topology in Topology::ALL.iter().cloned() {
+            let mut particules = Sma::new(8, 8, 3).unwrap();
+            particules.set_topology(topology);
+            particules.gen_agents(40).unwrap();
+            let mut wator = Sma::new_with_fish(8, 8, 3, 3, 4, 2).unwrap();
+            wator.set_topology(topology);
+            wator.gen_fish_agents(30, 5).unwrap();
+
+            for sma in [&mut particules, &mut wator].iter_mut() {
+                for _ in 0..30 {
+                    sma.tick();
+
+                    let filled = sma.get_state().iter().filter(|cell| !cell.is_empty_cell());
+                    assert_eq!(filled.count(), sma.agents.len(), "{}", topology);
+                    sma.agents.iter().for_each(|agent| {
+                        let coordinate = agent.coordinate();
+                        assert_eq!(sma.env.normalize(coordinate), Some(coordinate));
+                        match sma.env.get_cell(coordinate) {
+                            Some(Cell::Filled(occupant)) => {
+                                assert!(occupant.is_same(agent), "{}", topology)
+                            }
+                            _ => panic!("{} agent not found in its cell", topology),
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_record_tick_history() {
+        let mut sma = Sma::new_with_fish(10, 10, 3, 3, 4, 2).unwrap();
+        sma.gen_fish_agents(30, 5).unwrap();
+        let mut population = sma.agents.len();
+
+        for _ in 0..20 {
+            sma.tick();
+        }
+
+        assert_eq!(sma.history().len(), 20);
+        assert_eq!(
+            &TickSummary::from(sma.stats()),
+            sma.history().last().unwrap()
+        );
+        for (turn, stats) in sma.history().iter().enumerate() {
+            let living = stats.population(AgentKind::Fish) + stats.population(AgentKind::Shark);
+            assert_eq!(stats.turn, turn as i32);
+            assert_eq!(living, population + stats.births - stats.deaths);
+            assert_eq!(stats.deaths as u32, stats.starved + stats.eaten);
+            population = living;
+        }
+        assert_eq!(population, sma.agents.len());
+    }
+
+    #[test]
+    fn should_count_starved_sharks() {
+        let mut sma = Sma::new_with_fish(5, 5, 3, 3, 2, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(2, 2));
+
+        sma.tick();
+        assert_eq!(sma.stats().moves, 1);
+        assert_eq!(sma.stats().population(AgentKind::Shark), 1);
+
+        sma.tick();
+        assert_eq!(sma.stats().starved, 1);
+        assert_eq!(sma.stats().moves, 0);
+        assert_eq!(sma.stats().population(AgentKind::Shark), 0);
+    }
+
+    #[test]
+    fn should_breed_fish_after_breed_time() {
+        let mut sma = Sma::new_with_fish(5, 5, 3, 10, 10, 0).unwrap();
+        sma.add_fish_unsafe(Point::new(2, 2));
+
+        // The newborn acts from the next tick on, both breed again 3 ticks later
+        let population = (0..6)
+            .map(|_| {
+                sma.tick();
+                sma.stats().population(AgentKind::Fish)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(population, vec![1, 1, 2, 2, 2, 4]);
+    }
+
+    #[test]
+    fn should_breed_sharks_after_breed_time() {
+        let mut sma = Sma::new_with_fish(5, 5, 3, 2, 10, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(2, 2));
+
+        sma.tick();
+        assert_eq!(sma.stats().population(AgentKind::Shark), 1);
+        sma.tick();
+        assert_eq!(sma.stats().population(AgentKind::Shark), 2);
+    }
+
+    #[test]
+    fn should_starve_sharks_after_starve_time() {
+        let mut sma = Sma::new_with_fish(5, 5, 3, 10, 4, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(2, 2));
+
+        for _ in 0..3 {
+            sma.tick();
+            assert_eq!(sma.stats().population(AgentKind::Shark), 1);
+        }
+        sma.tick();
+        assert_eq!(sma.stats().starved, 1);
+        assert_eq!(sma.stats().population(AgentKind::Shark), 0);
+    }
+
+    #[test]
+    fn should_feed_sharks_eating_fish() {
+        // The fish has nowhere to go, the shark eats it on the first tick
+        let mut sma = Sma::new_with_fish(2, 1, 10, 10, 2, 0).unwrap();
+        let shark = sma.add_shark_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(1, 0));
+
+        sma.tick();
+        assert_eq!(sma.stats().eaten, 1);
+        assert_eq!(sma.agent(shark).unwrap().energy(), Some(2));
+
+        sma.tick();
+        assert_eq!(sma.stats().population(AgentKind::Shark), 1);
+        sma.tick();
+        assert_eq!(sma.stats().starved, 1);
+    }
+
+    #[test]
+    fn should_starve_sharks_out_of_energy() {
+        let mut sma = Sma::new_with_fish(5, 5, 3, 10, 10, 0).unwrap();
+        sma.set_energy_rules(Some(EnergyRules {
+            initial_energy: 3,
+            move_cost: 1,
+            ..EnergyRules::default()
+        }));
+        let shark = sma.add_shark_unsafe(Point::new(2, 2));
+
+        sma.tick();
+        assert_eq!(sma.agent(shark).unwrap().energy(), Some(1));
+        sma.tick();
+        assert_eq!(sma.stats().starved, 1);
+    }
+
+    #[test]
+    fn should_split_energy_between_parent_and_newborn() {
+        let mut sma = Sma::new_with_fish(2, 1, 10, 10, 10, 0).unwrap();
+        sma.set_energy_rules(Some(EnergyRules {
+            initial_energy: 10,
+            fish_energy: 4,
+            tick_cost: 1,
+            move_cost: 0,
+            breed_threshold: 10,
+            grass_energy: 0,
+        }));
+        let shark = sma.add_shark_unsafe(Point::new(0, 0));
+        sma.add_fish_unsafe(Point::new(1, 0));
+
+        // 9 energy left after the first tick, below the threshold, then 4 more from the fish
+        sma.tick();
+        assert_eq!(sma.agent(shark).unwrap().energy(), Some(13));
+
+        sma.tick();
+        assert_eq!(sma.stats().population(AgentKind::Shark), 2);
+        let energies = sma
+            .agents
+            .iter()
+            .map(|shark| shark.energy())
+            .collect::<Vec<_>>();
+        assert_eq!(energies, vec![Some(6), Some(6)]);
+    }
+
+    #[test]
+    fn should_graze_the_richest_cell() {
+        let mut sma = Sma::new_with_fish(3, 1, 10, 10, 10, 0).unwrap();
+        sma.set_resources(Some(ResourceRules {
+            regrowth: Regrowth::Delay(5),
+            ..ResourceRules::default()
+        }));
+        sma.set_track_changes(true);
+        let fish = sma.add_fish_unsafe(Point::new(1, 0));
+        sma.env.graze(Point::new(0, 0));
+
+        sma.tick();
+        assert_eq!(sma.agent(fish).unwrap().coordinate(), Point::new(2, 0));
+        assert_eq!(sma.env.resource(Point::new(0, 0)), Some(0.0));
+        assert_eq!(sma.env.resource(Point::new(1, 0)), Some(1.0));
+        assert_eq!(sma.env.resource(Point::new(2, 0)), Some(0.0));
+        assert_eq!(sma.changed_cells(), &[Point::new(1, 0), Point::new(2, 0)]);
+    }
+
+    #[test]
+    fn should_feed_fish_on_resources() {
+        // Alone on a single cell, the fish stays put and eats it out on the first tick
+        let mut sma = Sma::new_with_fish(1, 1, 10, 10, 10, 0).unwrap();
+        sma.set_energy_rules(Some(EnergyRules {
+            initial_energy: 2,
+            grass_energy: 3,
+            ..EnergyRules::default()
+        }));
+        sma.set_resources(Some(ResourceRules {
+            regrowth: Regrowth::Delay(100),
+            ..ResourceRules::default()
+        }));
+        let fish = sma.add_fish_unsafe(Point::new(0, 0));
+
+        sma.tick();
+        assert_eq!(sma.agent(fish).unwrap().energy(), Some(4));
+        for _ in 0..3 {
+            sma.tick();
+            assert_eq!(sma.stats().starved, 0);
+        }
+        sma.tick();
+        assert_eq!(sma.stats().starved, 1);
+    }
+
+    #[test]
+    fn should_saturate_counters_of_stalled_agents() {
+        // Alone on a single cell, the shark can neither move nor breed
+        let mut sma = Sma::new_with_fish(1, 1, 1, 1, 255, 0).unwrap();
+        let shark = sma.add_shark_unsafe(Point::new(0, 0));
+
+        for _ in 0..5 {
+            sma.tick();
+        }
+        let shark = sma.agent(shark).unwrap();
+        assert_eq!(shark.breed_count_down(), Some(0));
+        assert_eq!(shark.energy(), Some(250));
+        assert_eq!(sma.agents.len(), 1);
+    }
+
+    #[test]
+    fn should_spread_initial_ages() {
+        let mut sma = Sma::new_with_fish(10, 10, 5, 8, 3, 0).unwrap();
+        sma.gen_fish_agents(20, 0).unwrap();
+        assert!(sma
+            .agents
+            .iter()
+            .all(|fish| fish.breed_count_down() == Some(5)));
+
+        let mut sma = Sma::new_with_fish(10, 10, 5, 8, 3, 0).unwrap();
+        sma.set_random_age(true);
+        sma.gen_fish_agents(20, 0).unwrap();
+        let count_downs = sma
+            .agents
+            .iter()
+            .map(|fish| fish.breed_count_down().unwrap())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(count_downs.len() > 1);
+        assert!(count_downs
+            .iter()
+            .all(|count_down| (1..=5).contains(count_down)));
+    }
+
+    #[test]
+    fn should_run_until_a_condition_holds() {
+        let mut sma = Sma::new(5, 5, 0).unwrap();
+        sma.gen_agents(20).unwrap();
+        let outcome = sma.run_until(&[StopCondition::MaxTurns(4)]);
+        assert_eq!(outcome, RunOutcome::MaxTurns(4));
+        assert_eq!(sma.turn, 4);
+
+        let outcome = sma.run_until(&[StopCondition::custom("turn 6", |sma| sma.turn == 6)]);
+        assert_eq!(outcome, RunOutcome::Custom("turn 6".into()));
+        assert_eq!(sma.turn, 6);
+
+        let mut sma = Sma::new_with_fish(5, 5, 3, 3, 2, 0).unwrap();
+        sma.add_shark_unsafe(Point::new(2, 2));
+        let conditions = [StopCondition::Extinction, StopCondition::MaxTurns(10)];
+        assert_eq!(
+            sma.run_until(&conditions),
+            RunOutcome::Extinction(AgentKind::Shark)
+        );
+        assert_eq!(sma.turn, 2);
+    }
+
+    #[test]
+    fn should_detect_full_and_steady_grids() {
+        let mut sma = Sma::new(1, 1, 0).unwrap();
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        sma.gen_agent(Point::new(0, 0), right).unwrap();
+        assert_eq!(
+            sma.run_until(&[StopCondition::GridFull]),
+            RunOutcome::GridFull
+        );
+        assert_eq!(sma.turn, 0);
+
+        let conditions = [StopCondition::SteadyState(3), StopCondition::MaxTurns(10)];
+        assert_eq!(sma.run_until(&conditions), RunOutcome::SteadyState(3));
+        assert_eq!(sma.turn, 3);
+    }
+
+    #[test]
+    fn should_count_particule_actions() {
+        let mut sma = Sma::new(5, 5, 0).unwrap();
+        sma.set_scheduler(Scheduler::Sequential);
+        let right = Direction::new(HDirection::Right, VDirection::None);
+        let left = Direction::new(HDirection::Left, VDirection::None);
+        sma.gen_agent(Point::new(4, 0), right).unwrap();
+        sma.gen_agent(Point::new(1, 2), right).unwrap();
+        sma.gen_agent(Point::new(2, 2), left).unwrap();
+        sma.gen_agent(Point::new(0, 4), right).unwrap();
+
+        sma.tick();
+
+        let stats = sma.stats();
+        assert_eq!(stats.bounces, 1);
+        // The second agent of the pair has already been turned around and moves away
+        assert_eq!(stats.collisions, 1);
+        assert_eq!(stats.moves, 2);
+        assert_eq!(stats.stalls, 0);
+        assert_eq!(stats.population(AgentKind::Particule), 4);
+    }
+
+    #[test]
+    fn should_resume_saved_run() {
+        for format in SnapshotFormat::ALL.iter().cloned() {
+            let mut wator = Sma::new_with_fish(10, 10, 3, 3, 4, 2).unwrap();
+            wator.set_topology(Topology::Torus);
+            wator.gen_fish_agents(30, 5).unwrap();
+            let mut particules = Sma::new(10, 10, 7).unwrap();
+            particules.set_scheduler(Scheduler::Synchronous);
+            particules.gen_agents(30).unwrap();
+
+            for mut sma in [wator, particules] {
+                for _ in 0..10 {
+                    sma.tick();
+                }
+
+                let mut snapshot = vec![];
+                sma.save(&mut snapshot, format).unwrap();
+                let loaded = Sma::load(snapshot.as_slice(), format).unwrap();
+
+                assert_eq!(loaded.turn, 10);
+                assert_eq!(loaded.history(), sma.history());
+                assert_eq!(loaded.env.topology, sma.env.topology);
+                assert_eq!(loaded.env.shark_starve_time, sma.env.shark_starve_time);
+                for agent in sma.agents.iter() {
+                    assert!(loaded.agent(agent.id()).is_some(), "{}", format);
+                }
+                assert_eq!(run(loaded, 20), run(sma, 20), "{}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_snapshots() {
+        let garbage = "{\"turn\": 3}";
+        match Sma::load(garbage.as_bytes(), SnapshotFormat::Json) {
+            Err(Error::Snapshot(_)) => (),
+            _ => panic!("expected a snapshot error"),
+        }
+
+        let mut sma = Sma::new_with_fish(5, 5, 3, 3, 3, 0).unwrap();
+        sma.add_fish_unsafe(Point::new(1, 1));
+        sma.add_shark_unsafe(Point::new(2, 2));
+        sma.agents[1].set_coordinate(Point::new(1, 1));
+        let mut snapshot = vec![];
+        sma.save(&mut snapshot, SnapshotFormat::Binary).unwrap();
+
+        assert_eq!(
+            Sma::load(snapshot.as_slice(), SnapshotFormat::Binary).err(),
+            Some(Error::CellOccupied(Point::new(1, 1)))
+        );
     }
 }
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::particules::agent::Agent;
+use crate::wator::fish::Fish;
+use crate::wator::shark::Shark;
+use crate::AgentId;
+use crate::AgentRef;
+use crate::DeathCause;
+use crate::Error;
+
+/// Encoding used by `Sma::save` and `Sma::load`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Human readable, handy to share or tweak a configuration.
+    #[default]
+    Json,
+    /// Compact, for long runs.
+    Binary,
+}
+
+impl SnapshotFormat {
+    pub const ALL: [SnapshotFormat; 2] = [SnapshotFormat::Json, SnapshotFormat::Binary];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SnapshotFormat::Json => "json",
+            SnapshotFormat::Binary => "binary",
+        }
+    }
+}
+
+impl fmt::Display for SnapshotFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SnapshotFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        SnapshotFormat::ALL
+            .iter()
+            .find(|format| format.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownSnapshotFormat(value.into()))
+    }
+}
+
+/// Concrete state of an agent, the trait object behind an `AgentRef` cannot be serialized as is.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum AgentSnapshot {
+    Particule(Agent),
+    Fish(Fish),
+    Shark(Shark),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AgentRefSnapshot {
+    id: AgentId,
+    death_cause: Option<DeathCause>,
+    agent: AgentSnapshot,
+}
+
+impl From<AgentRef> for AgentRefSnapshot {
+    fn from(agent_ref: AgentRef) -> Self {
+        AgentRefSnapshot {
+            id: agent_ref.id(),
+            death_cause: agent_ref.death_cause(),
+            agent: agent_ref.snapshot(),
+        }
+    }
+}
+
+impl From<AgentRefSnapshot> for AgentRef {
+    fn from(snapshot: AgentRefSnapshot) -> Self {
+        let mut agent_ref = match snapshot.agent {
+            AgentSnapshot::Particule(agent) => AgentRef::from(agent, snapshot.id),
+            AgentSnapshot::Fish(fish) => AgentRef::from_fish(fish, snapshot.id),
+            AgentSnapshot::Shark(shark) => AgentRef::from_shark(shark, snapshot.id),
+        };
+        if let Some(cause) = snapshot.death_cause {
+            agent_ref.mark_for_removal(cause);
+        }
+        agent_ref
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::Action;
+use crate::AgentId;
+use crate::AgentKind;
+use crate::DeathCause;
+
+/// Counters gathered while running a single tick.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickStats {
+    pub turn: i32,
+    /// Living agents of each kind once the tick is over.
+    pub population: BTreeMap<AgentKind, usize>,
+    /// Contested cells settled by the conflict policy.
+    pub conflicts: u32,
+    pub births: Vec<AgentId>,
+    pub deaths: Vec<AgentId>,
+    pub starved: u32,
+    pub eaten: u32,
+    pub moves: u32,
+    pub stalls: u32,
+    /// Agents bumping into each other.
+    pub collisions: u32,
+    /// Agents bouncing off a wall.
+    pub bounces: u32,
+}
+
+impl TickStats {
+    pub fn population(&self, kind: AgentKind) -> usize {
+        self.population.get(&kind).cloned().unwrap_or(0)
+    }
+
+    pub(crate) fn record_action(&mut self, action: Action) {
+        match action {
+            Action::Moved => self.moves += 1,
+            Action::Stalled => self.stalls += 1,
+            Action::Collided => self.collisions += 1,
+            Action::Bounced => self.bounces += 1,
+        }
+    }
+
+    pub(crate) fn record_death(&mut self, id: AgentId, cause: DeathCause) {
+        self.deaths.push(id);
+        match cause {
+            DeathCause::Starvation => self.starved += 1,
+            DeathCause::Eaten => self.eaten += 1,
+        }
+    }
+}
+
+/// Counters of a past tick, as kept in the history: births and deaths are only counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickSummary {
+    pub turn: i32,
+    pub population: BTreeMap<AgentKind, usize>,
+    pub conflicts: u32,
+    pub births: usize,
+    pub deaths: usize,
+    pub starved: u32,
+    pub eaten: u32,
+    pub moves: u32,
+    pub stalls: u32,
+    pub collisions: u32,
+    pub bounces: u32,
+}
+
+impl TickSummary {
+    pub fn population(&self, kind: AgentKind) -> usize {
+        self.population.get(&kind).cloned().unwrap_or(0)
+    }
+}
+
+impl From<&TickStats> for TickSummary {
+    fn from(stats: &TickStats) -> Self {
+        TickSummary {
+            turn: stats.turn,
+            population: stats.population.clone(),
+            conflicts: stats.conflicts,
+            births: stats.births.len(),
+            deaths: stats.deaths.len(),
+            starved: stats.starved,
+            eaten: stats.eaten,
+            moves: stats.moves,
+            stalls: stats.stalls,
+            collisions: stats.collisions,
+            bounces: stats.bounces,
+        }
+    }
+}
+
+/// Mean number of ticks between two upward crossings of the mean of `series`,
+/// `None` until the population went up through its mean at least twice.
+pub fn oscillation_period(series: &[usize]) -> Option<f64> {
+    let crossings = mean_crossings(series);
+    match (crossings.first(), crossings.last()) {
+        (Some(first), Some(last)) if crossings.len() > 1 => {
+            Some((last - first) as f64 / (crossings.len() - 1) as f64)
+        }
+        _ => None,
+    }
+}
+
+/// Number of complete oscillations of `series` around its mean.
+pub fn cycles(series: &[usize]) -> usize {
+    mean_crossings(series).len().saturating_sub(1)
+}
+
+/// Upward crossings of a population through its running mean, counted as ticks arrive.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Oscillation {
+    ticks: usize,
+    sum: usize,
+    last: Option<usize>,
+    crossings: usize,
+    first_crossing: usize,
+    last_crossing: usize,
+}
+
+impl Oscillation {
+    pub fn record(&mut self, population: usize) {
+        self.sum += population;
+        let mean = self.sum as f64 / (self.ticks + 1) as f64;
+        if let Some(last) = self.last {
+            if (last as f64) < mean && population as f64 >= mean {
+                if self.crossings == 0 {
+                    self.first_crossing = self.ticks;
+                }
+                self.last_crossing = self.ticks;
+                self.crossings += 1;
+            }
+        }
+        self.last = Some(population);
+        self.ticks += 1;
+    }
+
+    /// Number of complete oscillations so far.
+    pub fn cycles(&self) -> usize {
+        self.crossings.saturating_sub(1)
+    }
+
+    /// Mean number of ticks between two upward crossings, `None` before the second one.
+    pub fn period(&self) -> Option<f64> {
+        if self.crossings > 1 {
+            Some((self.last_crossing - self.first_crossing) as f64 / (self.crossings - 1) as f64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Indices at which `series` goes up through its mean.
+fn mean_crossings(series: &[usize]) -> Vec<usize> {
+    if series.is_empty() {
+        return vec![];
+    }
+    let mean = series.iter().sum::<usize>() as f64 / series.len() as f64;
+    series
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| (pair[0] as f64) < mean && pair[1] as f64 >= mean)
+        .map(|(index, _)| index + 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::{cycles, oscillation_period, Oscillation};
+
+    #[test]
+    fn should_measure_oscillation_period() {
+        let series: Vec<usize> = (0..40).map(|tick| [2, 6, 10, 6, 2][tick % 5]).collect();
+
+        assert_eq!(oscillation_period(&series), Some(5.0));
+        assert_eq!(oscillation_period(&[4, 4, 4, 4]), None);
+        assert_eq!(oscillation_period(&[]), None);
+        assert_eq!(cycles(&series), 7);
+        assert_eq!(cycles(&[4, 4, 4, 4]), 0);
+    }
+
+    #[test]
+    fn should_track_oscillation_as_ticks_arrive() {
+        let mut oscillation = Oscillation::default();
+        for tick in 0..40 {
+            oscillation.record([2, 6, 10, 6, 2][tick % 5]);
+        }
+        assert_eq!(oscillation.period(), Some(5.0));
+        assert_eq!(oscillation.cycles(), 7);
+
+        let mut flat = Oscillation::default();
+        for _ in 0..4 {
+            flat.record(4);
+        }
+        assert_eq!(flat.period(), None);
+        assert_eq!(flat.cycles(), 0);
+    }
+}
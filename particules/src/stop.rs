@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::sma::Sma;
+use crate::AgentKind;
+
+/// Condition ending `Sma::run_until`.
+pub enum StopCondition {
+    /// The simulation reached the given turn.
+    MaxTurns(i32),
+    /// A kind of agent alive after the first tick has died out.
+    Extinction,
+    /// Every cell holds an agent.
+    GridFull,
+    /// No agent moved during the given number of ticks in a row.
+    SteadyState(usize),
+    /// The population of a kind oscillated around its running mean the given number of times.
+    PopulationCycle(usize),
+    /// Named user condition.
+    Custom(String, Box<dyn Fn(&Sma) -> bool>),
+}
+
+impl StopCondition {
+    pub fn custom<F>(name: &str, condition: F) -> Self
+    where
+        F: Fn(&Sma) -> bool + 'static,
+    {
+        StopCondition::Custom(name.into(), Box::new(condition))
+    }
+
+    /// Outcome of the run if the condition holds for `sma`.
+    pub fn check(&self, sma: &Sma) -> Option<RunOutcome> {
+        let history = sma.history();
+        match self {
+            StopCondition::MaxTurns(turns) if sma.turn >= *turns => {
+                Some(RunOutcome::MaxTurns(*turns))
+            }
+            StopCondition::Extinction => {
+                let first = history.first()?;
+                let last = history.last()?;
+                AgentKind::ALL
+                    .iter()
+                    .find(|&&kind| first.population(kind) > 0 && last.population(kind) == 0)
+                    .map(|&kind| RunOutcome::Extinction(kind))
+            }
+            StopCondition::GridFull if sma.agents.len() >= sma.get_state().len() => {
+                Some(RunOutcome::GridFull)
+            }
+            StopCondition::SteadyState(ticks) if *ticks > 0 && history.len() >= *ticks => {
+                let steady = history[history.len() - ticks..]
+                    .iter()
+                    .all(|stats| stats.moves == 0);
+                if steady {
+                    Some(RunOutcome::SteadyState(*ticks))
+                } else {
+                    None
+                }
+            }
+            StopCondition::PopulationCycle(count) => AgentKind::ALL.iter().find_map(|&kind| {
+                let oscillation = sma.oscillation(kind)?;
+                if *count > 0 && oscillation.cycles() >= *count {
+                    oscillation
+                        .period()
+                        .map(|period| RunOutcome::PopulationCycle { kind, period })
+                } else {
+                    None
+                }
+            }),
+            StopCondition::Custom(name, condition) if condition(sma) => {
+                Some(RunOutcome::Custom(name.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopCondition::MaxTurns(turns) => write!(f, "MaxTurns({})", turns),
+            StopCondition::Extinction => write!(f, "Extinction"),
+            StopCondition::GridFull => write!(f, "GridFull"),
+            StopCondition::SteadyState(ticks) => write!(f, "SteadyState({})", ticks),
+            StopCondition::PopulationCycle(count) => write!(f, "PopulationCycle({})", count),
+            StopCondition::Custom(name, _) => write!(f, "Custom({:?})", name),
+        }
+    }
+}
+
+/// Why `Sma::run_until` returned.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunOutcome {
+    MaxTurns(i32),
+    Extinction(AgentKind),
+    GridFull,
+    SteadyState(usize),
+    /// Mean number of ticks between two peaks of the population of `kind`.
+    PopulationCycle {
+        kind: AgentKind,
+        period: f64,
+    },
+    Custom(String),
+}
+
+impl fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunOutcome::MaxTurns(turns) => write!(f, "reached turn {}", turns),
+            RunOutcome::Extinction(kind) => write!(f, "{} died out", kind),
+            RunOutcome::GridFull => write!(f, "grid is full"),
+            RunOutcome::SteadyState(ticks) => write!(f, "no agent moved for {} ticks", ticks),
+            RunOutcome::PopulationCycle { kind, period } => {
+                write!(f, "{} population cycles every {:.1} ticks", kind, period)
+            }
+            RunOutcome::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
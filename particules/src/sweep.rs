@@ -0,0 +1,402 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::scenario::{Model, Scenario};
+use crate::stats::oscillation_period;
+use crate::validation::Validation;
+use crate::AgentKind;
+use crate::Error;
+
+/// Values taken by a parameter of a sweep: a single value, a list, or an inclusive range.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Values<T> {
+    One(T),
+    List(Vec<T>),
+    Range { from: T, to: T, step: Option<T> },
+}
+
+/// Addition that reports overflows, so that ranges ending near `T::MAX` terminate.
+pub trait CheckedAdd: Sized {
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! checked_add {
+    ($($t:ty),*) => {$(
+        impl CheckedAdd for $t {
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+        }
+    )*};
+}
+
+checked_add!(u8, i32);
+
+impl<T> Values<T>
+where
+    T: Copy + PartialOrd + CheckedAdd + From<u8>,
+{
+    pub fn expand(&self) -> Vec<T> {
+        match self {
+            Values::One(value) => vec![*value],
+            Values::List(values) => values.clone(),
+            Values::Range { from, to, step } => {
+                let step = step.unwrap_or_else(|| T::from(1));
+                let mut values = vec![];
+                let mut value = Some(*from);
+                // A null step would never reach the end of the range
+                while let Some(current) = value.filter(|value| value <= to && step > T::from(0)) {
+                    values.push(current);
+                    value = current.checked_add(step);
+                }
+                values
+            }
+        }
+    }
+}
+
+/// Wa-Tor parameters to combine, every combination is run `repetitions` times.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SweepSpec {
+    pub width: Values<i32>,
+    pub height: Values<i32>,
+    pub fish_breed_time: Values<u8>,
+    pub shark_breed_time: Values<u8>,
+    pub shark_starve_time: Values<u8>,
+    pub fish_density: Values<u8>,
+    pub shark_density: Values<u8>,
+    #[serde(default = "SweepSpec::default_repetitions")]
+    pub repetitions: u32,
+    /// Tick limit of every run.
+    pub ticks: u64,
+    /// Repetition `n` of every combination is seeded with `seed + n`.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl SweepSpec {
+    fn default_repetitions() -> u32 {
+        1
+    }
+
+    /// Every run of the sweep, in a stable order, once each combination is known to be valid.
+    pub fn runs(&self) -> Result<Vec<RunParameters>, Error> {
+        let widths = SweepSpec::values("width", &self.width)?;
+        let heights = SweepSpec::values("height", &self.height)?;
+        let fish_breed_times = SweepSpec::values("fish_breed_time", &self.fish_breed_time)?;
+        let shark_breed_times = SweepSpec::values("shark_breed_time", &self.shark_breed_time)?;
+        let shark_starve_times = SweepSpec::values("shark_starve_time", &self.shark_starve_time)?;
+        let fish_densities = SweepSpec::values("fish_density", &self.fish_density)?;
+        let shark_densities = SweepSpec::values("shark_density", &self.shark_density)?;
+
+        let mut runs = vec![];
+        for &width in widths.iter() {
+            for &height in heights.iter() {
+                for &fish_breed_time in fish_breed_times.iter() {
+                    for &shark_breed_time in shark_breed_times.iter() {
+                        for &shark_starve_time in shark_starve_times.iter() {
+                            for &fish_density in fish_densities.iter() {
+                                for &shark_density in shark_densities.iter() {
+                                    for repetition in 0..self.repetitions {
+                                        runs.push(RunParameters {
+                                            width,
+                                            height,
+                                            fish_breed_time,
+                                            shark_breed_time,
+                                            shark_starve_time,
+                                            fish_density,
+                                            shark_density,
+                                            seed: self.seed.wrapping_add(repetition as u64),
+                                            ticks: self.ticks,
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        SweepSpec::validate(&runs)?;
+        Ok(runs)
+    }
+
+    /// Run the whole sweep in parallel, results come back in the order of `runs`.
+    ///
+    /// A failed run does not stop the others.
+    pub fn run(&self) -> Result<Vec<Result<RunSummary, Error>>, Error> {
+        Ok(self.runs()?.par_iter().map(RunParameters::run).collect())
+    }
+
+    /// Report the problems of every combination, under the index of its first run.
+    fn validate(runs: &[RunParameters]) -> Result<(), Error> {
+        let mut validation = Validation::new();
+        let mut previous = None;
+        for (i, run) in runs.iter().enumerate() {
+            // Repetitions only differ by their seed
+            let combination = RunParameters {
+                seed: 0,
+                ..run.clone()
+            };
+            if previous.as_ref() == Some(&combination) {
+                continue;
+            }
+            let mut problems = Validation::new();
+            run.scenario().check(&mut problems);
+            for problem in problems.problems() {
+                let field = format!("runs[{}].{}", i, problem.field);
+                validation.report(field, problem.message.clone());
+            }
+            previous = Some(combination);
+        }
+        validation.finish()
+    }
+
+    fn values<T>(name: &str, values: &Values<T>) -> Result<Vec<T>, Error>
+    where
+        T: Copy + PartialOrd + CheckedAdd + From<u8>,
+    {
+        let values = values.expand();
+        if values.is_empty() {
+            Err(Error::InvalidSweep(format!("{} has no value", name)))
+        } else {
+            Ok(values)
+        }
+    }
+}
+
+/// A single run of a sweep.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RunParameters {
+    pub width: i32,
+    pub height: i32,
+    pub fish_breed_time: u8,
+    pub shark_breed_time: u8,
+    pub shark_starve_time: u8,
+    pub fish_density: u8,
+    pub shark_density: u8,
+    pub seed: u64,
+    pub ticks: u64,
+}
+
+impl RunParameters {
+    /// Wa-Tor scenario of the run.
+    pub fn scenario(&self) -> Scenario {
+        Scenario {
+            model: Model::Wator,
+            width: self.width,
+            height: self.height,
+            fish_density: self.fish_density,
+            shark_density: self.shark_density,
+            fish_breed_time: self.fish_breed_time,
+            shark_breed_time: self.shark_breed_time,
+            shark_starve_time: self.shark_starve_time,
+            seed: Some(self.seed),
+            ..Scenario::default()
+        }
+    }
+
+    /// Run until the tick limit, or until fish or sharks die out.
+    pub fn run(&self) -> Result<RunSummary, Error> {
+        let mut sma = self.scenario().build()?;
+
+        let mut extinction_turn = None;
+        for _ in 0..self.ticks {
+            sma.tick();
+            let stats = sma.stats();
+            if stats.population(AgentKind::Fish) == 0 || stats.population(AgentKind::Shark) == 0 {
+                extinction_turn = Some(stats.turn);
+                break;
+            }
+        }
+
+        let fish: Vec<usize> = sma
+            .history()
+            .iter()
+            .map(|stats| stats.population(AgentKind::Fish))
+            .collect();
+        let sharks: Vec<usize> = sma
+            .history()
+            .iter()
+            .map(|stats| stats.population(AgentKind::Shark))
+            .collect();
+        let mean = |series: &[usize]| match series.len() {
+            0 => 0.0,
+            len => series.iter().sum::<usize>() as f64 / len as f64,
+        };
+
+        Ok(RunSummary {
+            width: self.width,
+            height: self.height,
+            fish_breed_time: self.fish_breed_time,
+            shark_breed_time: self.shark_breed_time,
+            shark_starve_time: self.shark_starve_time,
+            fish_density: self.fish_density,
+            shark_density: self.shark_density,
+            seed: self.seed,
+            turns: sma.turn,
+            extinction_turn,
+            final_fish: fish.last().cloned().unwrap_or(0),
+            final_sharks: sharks.last().cloned().unwrap_or(0),
+            mean_fish: mean(&fish),
+            mean_sharks: mean(&sharks),
+            oscillation_period: oscillation_period(&fish),
+        })
+    }
+}
+
+/// One row of the sweep results, the run parameters followed by its outcome.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub width: i32,
+    pub height: i32,
+    pub fish_breed_time: u8,
+    pub shark_breed_time: u8,
+    pub shark_starve_time: u8,
+    pub fish_density: u8,
+    pub shark_density: u8,
+    pub seed: u64,
+    /// Ticks actually run.
+    pub turns: i32,
+    /// Turn at which fish or sharks died out, if they did.
+    pub extinction_turn: Option<i32>,
+    pub final_fish: usize,
+    pub final_sharks: usize,
+    pub mean_fish: f64,
+    pub mean_sharks: f64,
+    /// Mean number of ticks between two fish population peaks.
+    pub oscillation_period: Option<f64>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sweep::{RunSummary, SweepSpec, Values};
+    use crate::validation::Problem;
+    use crate::Error;
+
+    fn spec(json: &str) -> SweepSpec {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn should_expand_values() {
+        assert_eq!(Values::One(3u8).expand(), vec![3]);
+        assert_eq!(Values::List(vec![4, 2]).expand(), vec![4, 2]);
+        let range = Values::Range {
+            from: 2u8,
+            to: 8,
+            step: Some(3),
+        };
+        assert_eq!(range.expand(), vec![2, 5, 8]);
+        let range = Values::Range {
+            from: 1,
+            to: 3,
+            step: None,
+        };
+        assert_eq!(range.expand(), vec![1, 2, 3]);
+        let range = Values::Range {
+            from: 250u8,
+            to: 255,
+            step: None,
+        };
+        assert_eq!(range.expand(), vec![250, 251, 252, 253, 254, 255]);
+    }
+
+    #[test]
+    fn should_combine_every_parameter() {
+        let spec = spec(
+            r#"{
+                "width": 10, "height": 10,
+                "fish_breed_time": [2, 3],
+                "shark_breed_time": {"from": 3, "to": 5},
+                "shark_starve_time": 4,
+                "fish_density": 30, "shark_density": 5,
+                "repetitions": 2, "ticks": 10, "seed": 7
+            }"#,
+        );
+
+        let runs = spec.runs().unwrap();
+        assert_eq!(runs.len(), 2 * 3 * 2);
+        assert_eq!(runs[0].seed, 7);
+        assert_eq!(runs[1].seed, 8);
+        assert_eq!(runs.last().unwrap().fish_breed_time, 3);
+        assert_eq!(runs.last().unwrap().shark_breed_time, 5);
+
+        let spec = SweepSpec {
+            seed: u64::MAX,
+            ..spec
+        };
+        assert_eq!(spec.runs().unwrap()[1].seed, 0);
+
+        let summaries = spec.run().unwrap();
+        assert_eq!(summaries.len(), runs.len());
+        assert_eq!(spec.run().unwrap(), summaries);
+        let summaries: Vec<RunSummary> = summaries.into_iter().collect::<Result<_, _>>().unwrap();
+        for summary in summaries {
+            assert!(summary.turns <= 10);
+            assert!(summary.mean_fish > 0.0);
+        }
+    }
+
+    #[test]
+    fn should_parse_readme_example() {
+        let readme = include_str!("../../README.md");
+        let sweep = &readme[readme.find("`sweep`").unwrap()..];
+        let start = sweep.find("```json").unwrap() + "```json".len();
+        let end = start + sweep[start..].find("```").unwrap();
+
+        let runs = spec(&sweep[start..end]).runs().unwrap();
+        assert_eq!(runs.len(), 3 * 4 * 5);
+    }
+
+    #[test]
+    fn should_reject_empty_parameters() {
+        let spec = spec(
+            r#"{
+                "width": 10, "height": 10,
+                "fish_breed_time": [],
+                "shark_breed_time": 3, "shark_starve_time": 4,
+                "fish_density": 30, "shark_density": 5, "ticks": 10
+            }"#,
+        );
+
+        assert_eq!(
+            spec.runs().err(),
+            Some(Error::InvalidSweep("fish_breed_time has no value".into()))
+        );
+    }
+
+    #[test]
+    fn should_report_every_invalid_combination() {
+        let spec = spec(
+            r#"{
+                "width": [0, 10], "height": 10,
+                "fish_breed_time": 2, "shark_breed_time": 3, "shark_starve_time": 0,
+                "fish_density": [30, 120], "shark_density": 5,
+                "repetitions": 2, "ticks": 10
+            }"#,
+        );
+
+        let problem = |field: &str, message: &str| Problem {
+            field: field.into(),
+            message: message.into(),
+        };
+        let starve = "must be at least 1, got 0";
+        assert_eq!(
+            spec.run().err(),
+            Some(Error::InvalidConfig(vec![
+                problem("runs[0].width", "must be between 1 and 10000, got 0"),
+                problem("runs[0].shark_starve_time", starve),
+                problem("runs[2].width", "must be between 1 and 10000, got 0"),
+                problem("runs[2].shark_starve_time", starve),
+                problem("runs[4].shark_starve_time", starve),
+                problem("runs[6].shark_starve_time", starve),
+                problem(
+                    "runs[6].fish_density + shark_density",
+                    "must be at most 100 free cells, got 125"
+                ),
+            ]))
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How the edges of the environment connect to each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Every edge is a wall.
+    #[default]
+    Bounded,
+    /// Left meets right and top meets bottom.
+    Torus,
+    /// Left meets right, top and bottom are walls.
+    HorizontalCylinder,
+    /// Top meets bottom, left and right are walls.
+    VerticalCylinder,
+}
+
+impl Topology {
+    pub const ALL: [Topology; 4] = [
+        Topology::Bounded,
+        Topology::Torus,
+        Topology::HorizontalCylinder,
+        Topology::VerticalCylinder,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Topology::Bounded => "bounded",
+            Topology::Torus => "torus",
+            Topology::HorizontalCylinder => "horizontal_cylinder",
+            Topology::VerticalCylinder => "vertical_cylinder",
+        }
+    }
+
+    /// Whether an agent leaving through the left or right edge comes back on the other side.
+    pub fn wraps_x(&self) -> bool {
+        match self {
+            Topology::Torus | Topology::HorizontalCylinder => true,
+            Topology::Bounded | Topology::VerticalCylinder => false,
+        }
+    }
+
+    /// Whether an agent leaving through the top or bottom edge comes back on the other side.
+    pub fn wraps_y(&self) -> bool {
+        match self {
+            Topology::Torus | Topology::VerticalCylinder => true,
+            Topology::Bounded | Topology::HorizontalCylinder => false,
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Topology {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Topology::ALL
+            .iter()
+            .find(|topology| topology.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownTopology(value.into()))
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use crate::Error;
+
+/// Configuration field rejected by a validation pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Collects every problem of a configuration rather than stopping at the first one.
+#[derive(Debug, Default)]
+pub struct Validation {
+    problems: Vec<Problem>,
+}
+
+impl Validation {
+    pub fn new() -> Self {
+        Validation::default()
+    }
+
+    pub fn report<F: Into<String>, M: Into<String>>(&mut self, field: F, message: M) {
+        self.problems.push(Problem {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Report `value` unless it lies in `min..=max`, returns whether it does.
+    pub fn check_range<F, T>(&mut self, field: F, value: T, min: T, max: T) -> bool
+    where
+        F: Into<String>,
+        T: PartialOrd + fmt::Display,
+    {
+        let valid = min <= value && value <= max;
+        if !valid {
+            let message = format!("must be between {} and {}, got {}", min, max, value);
+            self.report(field, message);
+        }
+        valid
+    }
+
+    /// Report `value` if it is lower than `min`, returns whether it is not.
+    pub fn check_min<F, T>(&mut self, field: F, value: T, min: T) -> bool
+    where
+        F: Into<String>,
+        T: PartialOrd + fmt::Display,
+    {
+        let valid = min <= value;
+        if !valid {
+            self.report(field, format!("must be at least {}, got {}", min, value));
+        }
+        valid
+    }
+
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// `Error::InvalidConfig` listing every problem, if any was reported.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(self.problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::validation::{Problem, Validation};
+    use crate::Error;
+
+    #[test]
+    fn should_collect_every_problem() {
+        let mut validation = Validation::new();
+        assert!(validation.check_range("density", 50, 0, 100));
+        assert!(!validation.check_range("width", 0, 1, 10));
+        assert!(!validation.check_min("cycles", 0, 1));
+        validation.report("placements[0]", "cell 1:1 is already taken");
+
+        let error = validation.finish().unwrap_err();
+        let problem = |field: &str, message: &str| Problem {
+            field: field.into(),
+            message: message.into(),
+        };
+        assert_eq!(
+            error,
+            Error::InvalidConfig(vec![
+                problem("width", "must be between 1 and 10, got 0"),
+                problem("cycles", "must be at least 1, got 0"),
+                problem("placements[0]", "cell 1:1 is already taken"),
+            ])
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid configuration:\n  width: must be between 1 and 10, got 0\n  \
+             cycles: must be at least 1, got 0\n  placements[0]: cell 1:1 is already taken"
+        );
+        assert_eq!(Validation::new().finish(), Ok(()));
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How sharks survive and breed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rules {
+    /// Sharks starve after `shark_starve_time` ticks without eating and breed every
+    /// `shark_breed_time` ticks.
+    #[default]
+    Classic,
+    /// Sharks live on the energy of the fish they eat and breed by splitting it, see
+    /// `EnergyRules`.
+    Energy,
+}
+
+impl Rules {
+    pub const ALL: [Rules; 2] = [Rules::Classic, Rules::Energy];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Rules::Classic => "classic",
+            Rules::Energy => "energy",
+        }
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Rules {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Rules::ALL
+            .iter()
+            .find(|rules| rules.as_str() == value)
+            .cloned()
+            .ok_or_else(|| Error::UnknownRules(value.into()))
+    }
+}
+
+/// Parameters of `Rules::Energy`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyRules {
+    /// Energy of the sharks placed at start, and of the fish living on their energy.
+    pub initial_energy: u32,
+    /// Gained by a shark for every fish eaten.
+    pub fish_energy: u32,
+    /// Lost by every shark, and every fish living on its energy, on each tick.
+    pub tick_cost: u32,
+    /// Lost on top of `tick_cost` by a shark that moves.
+    pub move_cost: u32,
+    /// A shark holding at least this energy splits it with a newborn on its next move.
+    pub breed_threshold: u32,
+    /// Gained by a fish per unit of resource eaten. Fish live on their energy when this is
+    /// set and the environment has a resource layer, they never starve otherwise.
+    pub grass_energy: u32,
+}
+
+impl Default for EnergyRules {
+    fn default() -> Self {
+        EnergyRules {
+            initial_energy: 6,
+            fish_energy: 4,
+            tick_cost: 1,
+            move_cost: 0,
+            breed_threshold: 12,
+            grass_energy: 0,
+        }
+    }
+}
@@ -1,66 +1,119 @@
-use rand::{Rng, thread_rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{AgentCommand, AgentRef, Direction};
-use crate::core::AgentBehavior;
+use crate::{AgentCommand, AgentRef, DeathCause, SmaRng};
+use crate::core::{Action, AgentBehavior, Breeds, HasEnergy};
+use crate::snapshot::AgentSnapshot;
 use crate::environment::{Cell, Environment};
+use crate::wator::lifecycle::Lifecycle;
 use crate::Point;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Fish {
     pub coordinate: Point,
     pub decision: Decision,
-    pub breed_count_down: u8,
+    pub lifecycle: Lifecycle,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Decision {
     Stall,
     Move(Point),
     MoveAndBreed(Point),
 }
 
+impl Fish {
+    /// Fish live on the energy they find in the resource layer when the energy rules give it
+    /// any, they never starve otherwise.
+    pub fn newborn_lifecycle(environment: &Environment) -> Lifecycle {
+        let lifecycle = Lifecycle::fish(environment.fish_breed_time);
+        match (&environment.energy_rules, &environment.resources) {
+            (Some(rules), Some(_)) if rules.grass_energy > 0 => Lifecycle {
+                energy: Some(rules.initial_energy),
+                ..lifecycle
+            },
+            _ => lifecycle,
+        }
+    }
+
+    /// Newborn left on the current cell, it shares the energy of its parent if any.
+    fn give_birth(&mut self, environment: &Environment) -> Fish {
+        let mut lifecycle = Lifecycle::fish(environment.fish_breed_time);
+        if self.lifecycle.energy.is_some() {
+            lifecycle.energy = Some(self.lifecycle.split());
+        }
+        self.lifecycle.gave_birth(environment.fish_breed_time);
+        Fish {
+            coordinate: self.coordinate,
+            decision: Decision::Stall,
+            lifecycle,
+        }
+    }
+
+    /// Eat from the cell the fish stands on, once per tick whether it moved or not.
+    fn graze(&mut self, environment: &mut Environment) {
+        let eaten = environment.graze(self.coordinate);
+        if let Some(rules) = &environment.energy_rules {
+            self.lifecycle.gain((eaten * rules.grass_energy as f32).round() as u32);
+        }
+    }
+}
+
 impl AgentBehavior for Fish {
-    fn decide(&mut self, environment: &Environment) {
-        let empty_neighbors = vec![
-            self.peek(environment, 0, 1),
-            self.peek(environment, 0, -1),
-            self.peek(environment, 1, 0),
-            self.peek(environment, 1, -1),
-            self.peek(environment, 1, 1),
-            self.peek(environment, -1, 1),
-            self.peek(environment, -1, 0),
-            self.peek(environment, -1, -1),
-        ]
-        .iter()
-        .filter(|pos| pos.is_some())
-        .map(|pos| pos.unwrap())
-        .collect::<Vec<Point>>();
+    fn decide(&mut self, environment: &Environment, rng: &mut SmaRng) {
+        self.lifecycle.tick();
+        if let Some(rules) = &environment.energy_rules {
+            self.lifecycle.spend(rules.tick_cost);
+        }
+        let mut empty_neighbors = environment
+            .neighbours(self.coordinate, &environment.neighbourhood)
+            .into_iter()
+            .filter(|(cell, _)| cell.is_empty_cell())
+            .map(|(_, pos)| pos)
+            .collect::<Vec<Point>>();
+
+        // With a resource layer, fish head for the richest of the free cells
+        let level = |point: &Point| environment.resource(*point).unwrap_or_default();
+        let richest = empty_neighbors.iter().map(level).fold(0.0, f32::max);
+        empty_neighbors.retain(|point| level(point) >= richest);
 
         if empty_neighbors.is_empty() {
             self.decision = Decision::Stall;
         } else {
-            let idx = thread_rng().gen_range(0, empty_neighbors.len());
-            if self.breed_count_down == 0 {
+            let idx = rng.gen_range(0, empty_neighbors.len());
+            if self.lifecycle.can_breed() {
                 self.decision = Decision::MoveAndBreed(empty_neighbors[idx]);
-                self.breed_count_down = environment.fish_breed_time;
             } else {
                 self.decision = Decision::Move(empty_neighbors[idx]);
             }
-            self.breed_count_down -= 1;
         }
     }
 
     fn update(&mut self, environment: &mut Environment) -> AgentCommand {
-        match &self.decision {
+        if self.lifecycle.is_starving() {
+            if let Some(Cell::Filled(self_ref)) = environment.get_mut_cell(self.coordinate) {
+                self_ref.mark_for_removal(DeathCause::Starvation);
+            }
+            self.decision = Decision::Stall;
+            return AgentCommand::DoNothing;
+        }
+
+        let command = match &self.decision {
             Decision::Stall => AgentCommand::DoNothing,
+            // Another agent may have taken the cell since we decided
+            Decision::Move(position) | Decision::MoveAndBreed(position)
+                if !environment.is_empty(*position) =>
+            {
+                self.stall();
+                AgentCommand::DoNothing
+            }
             Decision::MoveAndBreed(position) => {
-                let child = Fish {
-                    coordinate: self.coordinate,
-                    decision: Decision::Stall,
-                    breed_count_down: environment.fish_breed_time,
-                };
-                let child_ref = AgentRef::from_fish(child);
-                environment.swap(self.coordinate, *position);
+                let position = *position;
+                let child = self.give_birth(environment);
+                let child_ref = AgentRef::from_fish(child, environment.new_agent_id());
+                environment.swap(self.coordinate, position);
                 environment.set_cell(self.coordinate, Cell::Filled(child_ref.clone())).unwrap();
-                self.coordinate = *position;
+                self.coordinate = position;
                 AgentCommand::Create(child_ref)
             }
             Decision::Move(position) => {
@@ -68,15 +121,9 @@ impl AgentBehavior for Fish {
                 self.coordinate = *position;
                 AgentCommand::DoNothing
             }
-        }
-    }
-
-    fn collision(&self) -> bool {
-        unimplemented!("collision")
-    }
-
-    fn direction(&self) -> Direction {
-        unimplemented!("direction")
+        };
+        self.graze(environment);
+        command
     }
 
     fn coordinate(&self) -> Point {
@@ -87,24 +134,56 @@ impl AgentBehavior for Fish {
         self.coordinate = point;
     }
 
-    fn previous_coordinate(&self) -> Point {
-        unimplemented!("previous")
+    fn get_color(&self) -> (f32, f32, f32) {
+        (0.0, 1.0, 0.0)
     }
 
-    fn set_previous_coordinate(&mut self, _: Point) {
-        unimplemented!("set previous ")
+    fn target(&self, _: &Environment) -> Option<Point> {
+        if self.lifecycle.is_starving() {
+            // Starving fish die during update and never reach their target
+            return None;
+        }
+
+        match self.decision {
+            Decision::Move(position) | Decision::MoveAndBreed(position) => Some(position),
+            Decision::Stall => None,
+        }
     }
 
-    fn set_collision(&mut self, _: bool) {
-        unimplemented!("set collision")
+    fn stall(&mut self) {
+        // A birth that could not happen is postponed to the next move, the count-down stays at 0
+        self.decision = Decision::Stall;
     }
 
-    fn set_direction(&mut self, _: Direction) {
-        unimplemented!("set direction")
+    fn last_action(&self) -> Action {
+        match self.decision {
+            Decision::Move(_) | Decision::MoveAndBreed(_) => Action::Moved,
+            Decision::Stall => Action::Stalled,
+        }
     }
 
-    fn get_color(&self) -> (f32, f32, f32) {
-        (0.0, 1.0, 0.0)
+    fn snapshot(&self) -> AgentSnapshot {
+        AgentSnapshot::Fish(self.clone())
+    }
+
+    fn as_energy(&self) -> Option<&dyn HasEnergy> {
+        // Only fish living on the resource layer can starve
+        self.lifecycle.energy.map(|_| self as &dyn HasEnergy)
+    }
+
+    fn as_breeds(&self) -> Option<&dyn Breeds> {
+        Some(self)
     }
+}
 
+impl HasEnergy for Fish {
+    fn energy(&self) -> u32 {
+        self.lifecycle.energy.unwrap_or_default()
+    }
+}
+
+impl Breeds for Fish {
+    fn breed_count_down(&self) -> u32 {
+        self.lifecycle.breed_count_down as u32
+    }
 }
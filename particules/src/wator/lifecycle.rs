@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+/// Age and count-downs of a Wa-Tor agent.
+///
+/// Counters saturate: once a count-down reaches 0 the agent stays ready to breed, or
+/// starving, until the matching rule resets it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lifecycle {
+    /// Ticks lived.
+    pub age: u32,
+    /// Ticks before the next birth, the agent breeds on its first move once it reaches 0.
+    pub breed_count_down: u8,
+    /// Ticks left before starving, `None` for agents that never starve.
+    pub starve_count_down: Option<u8>,
+    /// Energy left under `Rules::Energy`, the agent starves once it reaches 0.
+    #[serde(default)]
+    pub energy: Option<u32>,
+}
+
+impl Lifecycle {
+    pub fn fish(breed_time: u8) -> Self {
+        Lifecycle {
+            age: 0,
+            breed_count_down: breed_time,
+            starve_count_down: None,
+            energy: None,
+        }
+    }
+
+    pub fn shark(breed_time: u8, starve_time: u8) -> Self {
+        Lifecycle {
+            starve_count_down: Some(starve_time),
+            ..Lifecycle::fish(breed_time)
+        }
+    }
+
+    /// Agent living on its energy rather than count-downs.
+    pub fn energetic(energy: u32) -> Self {
+        Lifecycle {
+            energy: Some(energy),
+            ..Lifecycle::fish(0)
+        }
+    }
+
+    /// Same agent, `age` ticks after its birth, used to spread the first births of a population.
+    pub fn aged(self, age: u8) -> Self {
+        Lifecycle {
+            age: age as u32,
+            breed_count_down: self.breed_count_down.saturating_sub(age),
+            ..self
+        }
+    }
+
+    /// One tick older, called once per tick before the agent decides.
+    pub fn tick(&mut self) {
+        self.age = self.age.saturating_add(1);
+        self.breed_count_down = self.breed_count_down.saturating_sub(1);
+        self.starve_count_down = self.starve_count_down.map(|ticks| ticks.saturating_sub(1));
+    }
+
+    pub fn can_breed(&self) -> bool {
+        self.breed_count_down == 0
+    }
+
+    pub fn is_starving(&self) -> bool {
+        self.starve_count_down == Some(0) || self.energy == Some(0)
+    }
+
+    pub fn gave_birth(&mut self, breed_time: u8) {
+        self.breed_count_down = breed_time;
+    }
+
+    pub fn ate(&mut self, starve_time: u8) {
+        self.starve_count_down = Some(starve_time);
+    }
+
+    pub fn gain(&mut self, energy: u32) {
+        self.energy = self.energy.map(|left| left.saturating_add(energy));
+    }
+
+    pub fn spend(&mut self, energy: u32) {
+        self.energy = self.energy.map(|left| left.saturating_sub(energy));
+    }
+
+    /// Give half of the energy away to a newborn, returns its share.
+    pub fn split(&mut self) -> u32 {
+        let share = self.energy.unwrap_or_default() / 2;
+        self.spend(share);
+        share
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wator::lifecycle::Lifecycle;
+
+    #[test]
+    fn should_saturate_counters() {
+        let mut lifecycle = Lifecycle::shark(2, 1);
+        for _ in 0..300 {
+            lifecycle.tick();
+        }
+
+        assert_eq!(lifecycle.age, 300);
+        assert!(lifecycle.can_breed());
+        assert!(lifecycle.is_starving());
+
+        lifecycle.gave_birth(2);
+        lifecycle.ate(1);
+        assert!(!lifecycle.can_breed());
+        assert!(!lifecycle.is_starving());
+    }
+
+    #[test]
+    fn should_age_agents() {
+        let lifecycle = Lifecycle::fish(3).aged(2);
+        assert_eq!(lifecycle.age, 2);
+        assert_eq!(lifecycle.breed_count_down, 1);
+        assert!(!Lifecycle::fish(3).is_starving());
+        assert!(Lifecycle::fish(3).aged(5).can_breed());
+    }
+
+    #[test]
+    fn should_split_energy() {
+        let mut lifecycle = Lifecycle::energetic(7);
+        assert_eq!(lifecycle.split(), 3);
+        assert_eq!(lifecycle.energy, Some(4));
+
+        lifecycle.spend(10);
+        assert!(lifecycle.is_starving());
+        lifecycle.gain(2);
+        assert_eq!(lifecycle.energy, Some(2));
+
+        let mut fish = Lifecycle::fish(3);
+        fish.gain(2);
+        assert_eq!(fish.energy, None);
+    }
+}
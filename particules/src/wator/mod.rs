@@ -1,2 +1,4 @@
+pub mod energy;
 pub mod fish;
+pub mod lifecycle;
 pub mod shark;
@@ -1,43 +1,95 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::core::AgentBehavior;
+use crate::core::{Action, AgentBehavior, Breeds, HasEnergy};
 use crate::environment::{Cell, Environment};
+use crate::snapshot::AgentSnapshot;
+use crate::wator::lifecycle::Lifecycle;
 use crate::Point;
-use crate::{AgentCommand, AgentRef, Direction};
+use crate::{AgentCommand, AgentId, AgentRef, DeathCause, SmaRng};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Shark {
     pub coordinate: Point,
     pub decision: Decision,
-    pub breed_count_down: u8,
-    pub starve_time: u8,
+    pub lifecycle: Lifecycle,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Decision {
     Stall,
     Move(Point),
     MoveAndBreed(Point),
-    Eat(Point, AgentRef),
-    EatAndBreed(Point, AgentRef),
+    Eat(Point, AgentId),
+    EatAndBreed(Point, AgentId),
 }
 
-impl AgentBehavior for Shark {
-    fn decide(&mut self, environment: &Environment) {
-        let neighbors = vec![
-            self.peek_cell(environment, 0, 1),
-            self.peek_cell(environment, 0, -1),
-            self.peek_cell(environment, 1, 0),
-            self.peek_cell(environment, -1, 0),
-            self.peek_cell(environment, 1, 1),
-            self.peek_cell(environment, 1, -1),
-            self.peek_cell(environment, -1, 1),
-            self.peek_cell(environment, -1, -1),
-        ]
-        .iter()
-        .filter(|pos| pos.is_some())
-        .map(|pos| pos.unwrap())
-        .filter(|(cell, _)| cell.is_fish() || cell.is_empty_cell())
-        .collect::<Vec<(&Cell, Point)>>();
+impl Shark {
+    fn is_prey_at(environment: &Environment, position: Point, fish: AgentId) -> bool {
+        match environment.get_cell(position) {
+            Some(Cell::Filled(agent)) => agent.id() == fish,
+            _ => false,
+        }
+    }
+
+    fn eat(environment: &mut Environment, position: Point) {
+        if let Some(Cell::Filled(fish)) = environment.get_mut_cell(position) {
+            fish.mark_for_removal(DeathCause::Eaten);
+        }
+        environment.set_cell(position, Cell::Empty).unwrap();
+    }
+
+    fn can_breed(&self, environment: &Environment) -> bool {
+        match &environment.energy_rules {
+            Some(rules) => self.lifecycle.energy.unwrap_or_default() >= rules.breed_threshold,
+            None => self.lifecycle.can_breed(),
+        }
+    }
+
+    /// Newborn left on the current cell, it shares the energy of its parent under energy rules.
+    fn give_birth(&mut self, environment: &Environment) -> Shark {
+        let lifecycle = match &environment.energy_rules {
+            Some(_) => Lifecycle::energetic(self.lifecycle.split()),
+            None => {
+                self.lifecycle.gave_birth(environment.shark_breed_time);
+                Lifecycle::shark(environment.shark_breed_time, environment.shark_starve_time)
+            }
+        };
+        Shark {
+            coordinate: self.coordinate,
+            decision: Decision::Stall,
+            lifecycle,
+        }
+    }
+
+    fn feed(&mut self, environment: &Environment) {
+        match &environment.energy_rules {
+            Some(rules) => self.lifecycle.gain(rules.fish_energy),
+            None => self.lifecycle.ate(environment.shark_starve_time),
+        }
+    }
+
+    /// Move to `position`, paying for it under energy rules.
+    fn move_to(&mut self, environment: &mut Environment, position: Point) {
+        environment.swap(self.coordinate, position);
+        self.coordinate = position;
+        if let Some(rules) = &environment.energy_rules {
+            self.lifecycle.spend(rules.move_cost);
+        }
+    }
+}
 
+impl AgentBehavior for Shark {
+    fn decide(&mut self, environment: &Environment, rng: &mut SmaRng) {
+        self.lifecycle.tick();
+        if let Some(rules) = &environment.energy_rules {
+            self.lifecycle.spend(rules.tick_cost);
+        }
+        let neighbors = environment
+            .neighbours(self.coordinate, &environment.neighbourhood)
+            .into_iter()
+            .filter(|(cell, _)| cell.is_fish() || cell.is_empty_cell())
+            .collect::<Vec<(&Cell, Point)>>();
 
         if neighbors.is_empty() {
             self.decision = Decision::Stall
@@ -46,14 +98,11 @@ impl AgentBehavior for Shark {
                 .iter()
                 .filter(|(cell, _)| cell.is_fish())
                 .collect();
-            println!("fish neigh {}", fish_neighbors.len());
-            
-            let has_empty_neighbors = neighbors.len() - fish_neighbors.len() != 0;
 
-            println!("empty neigh {}", fish_neighbors.len());
+            let has_empty_neighbors = neighbors.len() - fish_neighbors.len() != 0;
 
             if !fish_neighbors.is_empty() {
-                let idx = thread_rng().gen_range(0, fish_neighbors.len());
+                let idx = rng.gen_range(0, fish_neighbors.len());
 
                 let cell = fish_neighbors[idx].0;
 
@@ -62,33 +111,27 @@ impl AgentBehavior for Shark {
                     _ => unreachable!("Expected a fish to eat"),
                 };
 
-                if self.breed_count_down == 0 {
-                    self.decision = Decision::EatAndBreed(fish_neighbors[idx].1, fish.clone());
-                    self.breed_count_down = environment.shark_breed_time;
+                if self.can_breed(environment) {
+                    self.decision = Decision::EatAndBreed(fish_neighbors[idx].1, fish.id());
                 } else {
-                    self.decision = Decision::Eat(fish_neighbors[idx].1, fish.clone());
+                    self.decision = Decision::Eat(fish_neighbors[idx].1, fish.id());
                 }
             } else if has_empty_neighbors {
-
-                let idx = thread_rng().gen_range(0, neighbors.len());
-                if self.breed_count_down == 0 {
+                let idx = rng.gen_range(0, neighbors.len());
+                if self.can_breed(environment) {
                     self.decision = Decision::MoveAndBreed(neighbors[idx].1);
-                    self.breed_count_down = environment.shark_breed_time;
                 } else {
                     self.decision = Decision::Move(neighbors[idx].1);
                 }
             }
         }
-
-        self.starve_time -= 1;
-        self.breed_count_down -= 1;
     }
 
     fn update(&mut self, environment: &mut Environment) -> AgentCommand {
-        if self.starve_time == 0 {
+        if self.lifecycle.is_starving() {
             let self_ref = environment.get_mut_cell(self.coordinate);
             if let Some(Cell::Filled(self_ref)) = self_ref {
-                self_ref.clone().mark_for_removal();
+                self_ref.mark_for_removal(DeathCause::Starvation);
                 self.decision = Decision::Stall;
                 return AgentCommand::DoNothing;
             } else {
@@ -96,24 +139,28 @@ impl AgentBehavior for Shark {
             };
         };
 
-         match &mut self.decision {
+        match &mut self.decision {
             Decision::Stall => AgentCommand::DoNothing,
+            // Another agent may have taken the cell, or the fish, since we decided
+            Decision::Move(position) | Decision::MoveAndBreed(position)
+                if !environment.is_empty(*position) =>
+            {
+                self.decision = Decision::Stall;
+                AgentCommand::DoNothing
+            }
+            Decision::Eat(position, fish) | Decision::EatAndBreed(position, fish)
+                if !Shark::is_prey_at(environment, *position, *fish) =>
+            {
+                self.decision = Decision::Stall;
+                AgentCommand::DoNothing
+            }
             Decision::MoveAndBreed(position) => {
-                println!("MOVE AND BREED");
-
-
-                let child = Shark {
-                    coordinate: self.coordinate,
-                    decision: Decision::Stall,
-                    breed_count_down: environment.shark_breed_time,
-                    starve_time: environment.shark_starve_time,
-                };
-
+                let position = *position;
+                let child = self.give_birth(environment);
+                let child_ref = AgentRef::from_shark(child, environment.new_agent_id());
                 let child_position = self.coordinate;
-                let child_ref = AgentRef::from_shark(child);
-
-                environment.set_agent_cell(self.coordinate, *position);
 
+                self.move_to(environment, position);
                 environment
                     .set_cell(child_position, Cell::Filled(child_ref.clone()))
                     .unwrap();
@@ -121,53 +168,36 @@ impl AgentBehavior for Shark {
                 AgentCommand::Create(child_ref)
             }
             Decision::Move(position) => {
-                environment.set_agent_cell(self.coordinate, *position);
+                let position = *position;
+                self.move_to(environment, position);
                 AgentCommand::DoNothing
             }
-            Decision::Eat(position, fish) => {
-                println!("EAT");
-
-                environment.set_agent_cell(self.coordinate, *position);
-                self.starve_time = environment.shark_starve_time;
-                fish.mark_for_removal();
+            Decision::Eat(position, _) => {
+                let position = *position;
+                Shark::eat(environment, position);
+                self.move_to(environment, position);
+                self.feed(environment);
 
                 AgentCommand::DoNothing
             }
-            Decision::EatAndBreed(position, fish) => {
-                println!("EAT AND BREED");
-
-                environment.set_cell(*position, Cell::Empty).unwrap();
-                let child = Shark {
-                    coordinate: self.coordinate,
-                    decision: Decision::Stall,
-                    breed_count_down: environment.shark_breed_time,
-                    starve_time: environment.shark_starve_time,
-                };
-                let child_ref = AgentRef::from_shark(child);
+            Decision::EatAndBreed(position, _) => {
+                let position = *position;
+                Shark::eat(environment, position);
+                let child = self.give_birth(environment);
+                let child_ref = AgentRef::from_shark(child, environment.new_agent_id());
                 let child_position = self.coordinate;
 
-                environment.set_agent_cell(self.coordinate, *position);
-
+                self.move_to(environment, position);
                 environment
                     .set_cell(child_position, Cell::Filled(child_ref.clone()))
                     .unwrap();
-                self.coordinate = *position;
-                self.starve_time = environment.shark_starve_time;
-                fish.mark_for_removal();
+                self.feed(environment);
 
                 AgentCommand::Create(child_ref)
             }
         }
     }
 
-    fn collision(&self) -> bool {
-        unimplemented!("collision")
-    }
-
-    fn direction(&self) -> Direction {
-        unimplemented!("direction")
-    }
-
     fn coordinate(&self) -> Point {
         self.coordinate
     }
@@ -176,23 +206,63 @@ impl AgentBehavior for Shark {
         self.coordinate = point;
     }
 
-    fn previous_coordinate(&self) -> Point {
-        unimplemented!("previous")
+    fn get_color(&self) -> (f32, f32, f32) {
+        (1.0, 0.0, 0.0)
+    }
+
+    fn target(&self, _: &Environment) -> Option<Point> {
+        if self.lifecycle.is_starving() {
+            // Starving sharks die during update and never reach their target
+            return None;
+        }
+
+        match &self.decision {
+            Decision::Move(position)
+            | Decision::MoveAndBreed(position)
+            | Decision::Eat(position, _)
+            | Decision::EatAndBreed(position, _) => Some(*position),
+            Decision::Stall => None,
+        }
     }
 
-    fn set_previous_coordinate(&mut self, _: Point) {
-        unimplemented!("set previous ")
+    fn stall(&mut self) {
+        self.decision = Decision::Stall;
     }
 
-    fn set_collision(&mut self, _: bool) {
-        unimplemented!("set collision")
+    fn last_action(&self) -> Action {
+        match self.decision {
+            Decision::Stall => Action::Stalled,
+            Decision::Move(_)
+            | Decision::MoveAndBreed(_)
+            | Decision::Eat(_, _)
+            | Decision::EatAndBreed(_, _) => Action::Moved,
+        }
     }
 
-    fn set_direction(&mut self, _: Direction) {
-        unimplemented!("set direction")
+    fn snapshot(&self) -> AgentSnapshot {
+        AgentSnapshot::Shark(self.clone())
     }
 
-    fn get_color(&self) -> (f32, f32, f32) {
-        (1.0, 0.0, 0.0)
+    fn as_energy(&self) -> Option<&dyn HasEnergy> {
+        Some(self)
+    }
+
+    fn as_breeds(&self) -> Option<&dyn Breeds> {
+        Some(self)
+    }
+}
+
+impl HasEnergy for Shark {
+    fn energy(&self) -> u32 {
+        match self.lifecycle.energy {
+            Some(energy) => energy,
+            None => self.lifecycle.starve_count_down.unwrap_or_default() as u32,
+        }
+    }
+}
+
+impl Breeds for Shark {
+    fn breed_count_down(&self) -> u32 {
+        self.lifecycle.breed_count_down as u32
     }
 }
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::str::FromStr;
+
+use particules::Point;
+use stdweb::web::html_element::CanvasElement;
+use stdweb::web::CanvasRenderingContext2d;
+use yew::NodeRef;
+
+use super::cell::Color;
+
+/// Side of a cell drawn on the canvas, in pixels.
+pub const CELL_SIZE: f64 = 4.0;
+
+/// Largest world, in cells, that the grid opens with the `Cells` renderer.
+pub const MAX_CELLS_RENDERED: i32 = 2_500;
+
+/// How the grid draws the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Renderer {
+    /// One `CellComponent` per cell, only suited to small worlds.
+    Cells,
+    /// A single `<canvas>`, scales to hundreds of thousands of cells.
+    Canvas,
+}
+
+impl Renderer {
+    pub const ALL: [Renderer; 2] = [Renderer::Cells, Renderer::Canvas];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Renderer::Cells => "cells",
+            Renderer::Canvas => "canvas",
+        }
+    }
+
+    /// Renderer a world of the given size opens with.
+    pub fn for_world(width: i32, height: i32) -> Self {
+        if width * height > MAX_CELLS_RENDERED {
+            Renderer::Canvas
+        } else {
+            Renderer::default()
+        }
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::Cells
+    }
+}
+
+impl fmt::Display for Renderer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Renderer {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Renderer::ALL
+            .iter()
+            .find(|renderer| renderer.as_str() == value)
+            .cloned()
+            .ok_or_else(|| format!("Unknown renderer '{}'", value))
+    }
+}
+
+#[derive(Default)]
+pub struct Canvas {
+    pub node: NodeRef,
+}
+
+impl Canvas {
+    /// Cell under a click at `x`, `y` pixels from the top left corner of the canvas.
+    pub fn cell_at(x: f64, y: f64) -> (i32, i32) {
+        ((x / CELL_SIZE) as i32, (y / CELL_SIZE) as i32)
+    }
+
+    /// Size in pixels of a canvas showing `cells` cells.
+    pub fn pixels(cells: i32) -> String {
+        (cells as f64 * CELL_SIZE).to_string()
+    }
+
+    pub fn clear(&self) {
+        if let Some((canvas, context)) = self.context() {
+            context.set_fill_style_color(&Color::None.css());
+            let (width, height) = (canvas.width() as f64, canvas.height() as f64);
+            context.fill_rect(0.0, 0.0, width, height);
+        }
+    }
+
+    /// Fill every listed cell, the context is only fetched once.
+    pub fn paint(&self, cells: &[(Point, Color)]) {
+        if let Some((_, context)) = self.context() {
+            for (point, color) in cells {
+                context.set_fill_style_color(&color.css());
+                let (x, y) = (point.x as f64 * CELL_SIZE, point.y as f64 * CELL_SIZE);
+                context.fill_rect(x, y, CELL_SIZE, CELL_SIZE);
+            }
+        }
+    }
+
+    fn context(&self) -> Option<(CanvasElement, CanvasRenderingContext2d)> {
+        let canvas: CanvasElement = self.node.try_into()?;
+        let context = canvas.get_context().ok()?;
+        Some((canvas, context))
+    }
+}
@@ -1,3 +1,6 @@
+use particules::environment::{Cell, Environment};
+use particules::resource;
+use particules::Point;
 use yew::{html, macros::Properties, Callback, Component, ComponentLink, Html, ShouldRender};
 
 pub struct CellComponent {
@@ -47,25 +50,39 @@ impl Component for CellComponent {
 
 #[derive(Clone)]
 pub enum Color {
-    Red,
-    Black,
     None,
+    /// Colour of the agent on the cell, as given by `get_color`.
+    Agent((f32, f32, f32)),
+    /// Empty cell holding this share of its resource capacity.
+    Resource(f32),
 }
 
 impl Color {
-    pub fn from(collision: bool) -> Self {
-        if collision {
-            Color::Red
-        } else {
-            Color::Black
+    pub fn at(environment: &Environment, point: Point) -> Self {
+        match (environment.get_cell(point), environment.resource(point)) {
+            (Some(Cell::Filled(agent)), _) => Color::Agent(agent.get_color()),
+            (_, Some(fraction)) if fraction > 0.0 => Color::Resource(fraction),
+            _ => Color::None,
         }
     }
 
     pub fn as_str(&self) -> &str {
         match self {
-            Color::Black => "cell-black",
-            Color::Red => "cell-red",
-            Color::None => "cell-empty",
+            Color::Agent(_) => "cell-filled",
+            Color::Resource(_) | Color::None => "cell-empty",
+        }
+    }
+
+    /// Css colour of the cell, white when empty.
+    pub fn css(&self) -> String {
+        let rgb = |(r, g, b): (f32, f32, f32)| {
+            let channel = |value: f32| (value * 255.0) as u8;
+            format!("rgb({}, {}, {})", channel(r), channel(g), channel(b))
+        };
+        match self {
+            Color::Agent(color) => rgb(*color),
+            Color::Resource(fraction) => rgb(resource::tint(*fraction)),
+            Color::None => "white".into(),
         }
     }
 }
@@ -1,42 +1,59 @@
 use log::trace;
+use particules::scenario::{Model, Scenario};
+use particules::scheduler::Scheduler;
 use particules::sma::Sma;
+use particules::topology::Topology;
+use particules::AgentKind;
 use particules::AgentRef;
 use particules::Direction;
 use particules::HDirection;
 use particules::Point;
 use particules::VDirection;
-use particules::SMA;
 
 use std::time::Duration;
 use yew::{
     html,
     macros::Properties,
+    events::ClickEvent,
     services::{IntervalService, Task},
-    Component, ComponentLink, Html, NodeRef, ShouldRender,
+    ChangeData, Component, ComponentLink, Html, NodeRef, ShouldRender,
 };
 
+use super::canvas::{Canvas, Renderer};
 use super::cell::CellComponent;
 use super::cell::Color;
+use stdweb::web::event::IMouseEvent;
 use stdweb::web::Element;
 use stdweb::web::IElement;
+use stdweb::web::INode;
 
 pub struct Grid {
     link: ComponentLink<Self>,
     props: Props,
+    sma: Sma,
     direction: Direction,
+    scheduler: Scheduler,
+    topology: Topology,
+    renderer: Renderer,
+    canvas: Canvas,
+    /// Repaint the whole world on the next interval tick, once the new renderer is in the DOM.
+    redraw: bool,
     active: bool,
-    borderless: bool,
+    debug: bool,
+    /// Kind of agent placed by a click in Wa-Tor mode.
+    placing: AgentKind,
     error: String,
     refs: Vec<NodeRef>,
+    counters: NodeRef,
     #[allow(unused)]
     job: Box<dyn Task>,
 }
 
 #[derive(Properties, Clone)]
 pub struct Props {
-    pub width: i32,
-    pub height: i32,
     pub agents: Vec<AgentRef>,
+    /// Validated by the environment form.
+    pub scenario: Scenario,
 }
 
 pub enum Msg {
@@ -45,8 +62,12 @@ pub enum Msg {
     Clear,
     Step,
     Tick,
-    Borderless,
     ChangeDir(Direction),
+    ChangeScheduler(Scheduler),
+    ChangeTopology(Topology),
+    ToggleDebug,
+    ChangeRenderer(Renderer),
+    ChangePlacing(AgentKind),
 }
 
 impl Component for Grid {
@@ -57,18 +78,33 @@ impl Component for Grid {
         let callback = link.callback(|_| Msg::Tick);
         let mut interval = IntervalService::new();
         let handle = interval.spawn(Duration::from_millis(70), callback);
-        Sma::new(props.width, props.height);
-        let refs = Self::init_refs();
+        let (mut sma, error) = match props.scenario.build() {
+            Ok(sma) => (sma, String::new()),
+            Err(e) => (Self::empty_sma(&props), e.to_string()),
+        };
+        // Only the cells changed by a tick are repainted
+        sma.set_track_changes(true);
+        let refs = Self::init_refs(&sma);
         let direction = Direction::new(HDirection::Right, VDirection::None);
+        let (scheduler, topology) = (props.scenario.scheduler, props.scenario.topology);
+        let renderer = Renderer::for_world(props.scenario.width, props.scenario.height);
 
         Grid {
             link,
             props,
+            sma,
             direction,
-            error: "".into(),
+            scheduler,
+            topology,
+            renderer,
+            canvas: Canvas::default(),
+            redraw: false,
+            error,
             refs,
+            counters: NodeRef::default(),
             active: false,
-            borderless: false,
+            debug: false,
+            placing: AgentKind::Fish,
             job: Box::new(handle),
         }
     }
@@ -80,17 +116,36 @@ impl Component for Grid {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
+            Msg::AddAgent((x, y)) if self.is_wator() => {
+                let coordinate = Point { x, y };
+                let placed = match self.placing {
+                    AgentKind::Shark => self.sma.gen_shark(coordinate),
+                    _ => self.sma.gen_fish(coordinate),
+                };
+                match placed {
+                    Ok(_) => {
+                        self.error = "".into();
+                        self.restyle(&[coordinate]);
+                    }
+                    Err(e) => self.error = e.to_string(),
+                }
+                return true;
+            }
             Msg::AddAgent((x, y)) => {
                 if self.direction != Direction::new(HDirection::None, VDirection::None) {
                     let coordinate = Point { x, y };
 
-                    if let Ok(()) = SMA.write().unwrap().gen_agent(coordinate, self.direction) {
-                        self.draw_agents();
+                    match self.sma.gen_agent(coordinate, self.direction) {
+                        Ok(_) => {
+                            self.error = "".into();
+                            self.restyle(&[coordinate]);
+                        }
+                        Err(e) => self.error = e.to_string(),
                     }
                 } else {
                     self.error = "Please chose a direction".into()
                 }
-                return false;
+                return true;
             }
             Msg::Play => {
                 self.active = !self.active;
@@ -98,36 +153,60 @@ impl Component for Grid {
             }
             Msg::Clear => {
                 self.active = false;
-                self.clear_filled_cells();
-                Sma::new(self.props.width, self.props.height);
+                self.clear_agents();
+                self.sma = Self::empty_sma(&self.props);
+                self.sma.set_track_changes(true);
+                self.sma.set_scheduler(self.scheduler);
+                self.sma.set_topology(self.topology);
+                // The resources of the new environment start full
+                self.redraw = self.sma.env.has_resources();
                 return true;
             }
             Msg::Step => {
                 if self.active {
                     self.active = false;
                 }
-                self.clear_filled_cells();
-                SMA.write().unwrap().tick();
-                self.draw_agents();
+                self.sma.tick();
+                self.draw_changes();
+                self.draw_counters();
                 return true;
             }
             Msg::Tick => {
-                trace!("coucou");
-                if self.active {
-                    self.clear_filled_cells();
-                    trace!("coucou");
-                    SMA.write().unwrap().tick();
-                    trace!("coucou");
+                if self.redraw {
+                    self.redraw = false;
                     self.draw_agents();
-                    trace!("coucou");
+                }
+                if self.active {
+                    self.sma.tick();
+                    self.draw_changes();
+                    self.draw_counters();
                 }
             }
             Msg::ChangeDir(dir) => {
                 self.direction = dir;
                 return true;
             }
-            Msg::Borderless => {
-                SMA.write().unwrap().set_borderless(!self.borderless);
+            Msg::ChangeScheduler(scheduler) => {
+                self.scheduler = scheduler;
+                self.sma.set_scheduler(scheduler);
+                return true;
+            }
+            Msg::ChangeTopology(topology) => {
+                self.topology = topology;
+                self.sma.set_topology(topology);
+                return true;
+            }
+            Msg::ToggleDebug => {
+                self.debug = !self.debug;
+                return true;
+            }
+            Msg::ChangeRenderer(renderer) => {
+                self.renderer = renderer;
+                self.redraw = true;
+                return true;
+            }
+            Msg::ChangePlacing(kind) => {
+                self.placing = kind;
                 return true;
             }
         }
@@ -142,8 +221,81 @@ impl Component for Grid {
                         <button class="game-button" onclick=self.link.callback(|_| Msg::Play)>{ if !self.active {{"Play"}} else {{"Pause"}} }</button>
                         <button class="game-button" onclick=self.link.callback(|_| Msg::Clear)>{ "Clear" }</button>
                         <button class="game-button" onclick=self.link.callback(|_| Msg::Step)>{ "Step" }</button>
-                        <button class="game-button" onclick=self.link.callback(|_| Msg::Borderless)>{ "Borderless" }</button>
+                        <button class="game-button" onclick=self.link.callback(|_| Msg::ToggleDebug)>{ if self.debug {{"Hide agents"}} else {{"Show agents"}} }</button>
+                        <select class="game-button" onchange=self.link.callback(Self::on_scheduler_change)>
+                            {for Scheduler::ALL.iter().map(|scheduler| html! {
+                                <option value=scheduler.as_str()>{scheduler}</option>
+                            })}
+                        </select>
+                        <select class="game-button" onchange=self.link.callback(Self::on_topology_change)>
+                            {for Topology::ALL.iter().map(|topology| html! {
+                                <option value=topology.as_str()>{topology}</option>
+                            })}
+                        </select>
+                        <select class="game-button" onchange=self.link.callback(Self::on_renderer_change)>
+                            {for Renderer::ALL.iter().map(|renderer| html! {
+                                <option value=renderer.as_str() selected={*renderer == self.renderer}>{renderer}</option>
+                            })}
+                        </select>
+                </div>
+                {self.view_placement()}
+            </div>
+            <p color="red"> {"error : " } {&self.error}</p>
+            <p ref=self.counters.clone()>{self.counters()}</p>
+            {self.view_world()}
+            <div>
+                {self.view_debug()}
+            </div>
+            </section>
+        }
+    }
+}
+
+impl Grid {
+    fn on_scheduler_change(data: ChangeData) -> Msg {
+        let scheduler = match data {
+            ChangeData::Select(select) => {
+                select.value().and_then(|value| value.parse().ok())
+            }
+            _ => None,
+        };
+        Msg::ChangeScheduler(scheduler.unwrap_or_default())
+    }
+
+    fn on_topology_change(data: ChangeData) -> Msg {
+        let topology = match data {
+            ChangeData::Select(select) => {
+                select.value().and_then(|value| value.parse().ok())
+            }
+            _ => None,
+        };
+        Msg::ChangeTopology(topology.unwrap_or_default())
+    }
+
+    fn on_renderer_change(data: ChangeData) -> Msg {
+        let renderer = match data {
+            ChangeData::Select(select) => {
+                select.value().and_then(|value| value.parse().ok())
+            }
+            _ => None,
+        };
+        Msg::ChangeRenderer(renderer.unwrap_or_default())
+    }
+
+    fn view_placement(&self) -> Html {
+        if self.is_wator() {
+            return html! {
+                <div class="game-buttons">
+                    {for [AgentKind::Fish, AgentKind::Shark].iter().map(|&kind| html! {
+                        <button class="game-button" onclick=self.link.callback(move |_| Msg::ChangePlacing(kind))>
+                            { if self.placing == kind {{"> "}} else {{""}} }{ format!("Place {}", kind) }
+                        </button>
+                    })}
                 </div>
+            };
+        }
+        html! {
+            <div>
                 <div class="">
                     <div class="row">
                         <button class="" onclick=self.link.callback(|_| Msg::ChangeDir(Direction::new(HDirection::Left, VDirection::Up)))><i class="left-up"></i></button>
@@ -161,26 +313,37 @@ impl Component for Grid {
                     <button class="" onclick=self.link.callback(|_| Msg::ChangeDir(Direction::new(HDirection::Right, VDirection::Down)))><i class="right-down"></i></button>
                     </div>
                 </div>
+                <div>
+                    <i class={self.dir_to_arrow(self.direction)}> </i>
+                </div>
             </div>
-            <div>
-                <i class={self.dir_to_arrow(self.direction)}> </i>
-            </div>
-            <div class="particules">
-                {(0..self.props.width).map(|row| self.view_row(row)).collect::<Html>()}
-            </div>
-            <div>
-                {self.view_debug()}
-            </div>
-            </section>
         }
     }
-}
 
-impl Grid {
+    fn view_world(&self) -> Html {
+        match self.renderer {
+            Renderer::Cells => html! {
+                <div class="particules">
+                    {(0..self.props.scenario.height).map(|row| self.view_row(row)).collect::<Html>()}
+                </div>
+            },
+            Renderer::Canvas => html! {
+                <canvas class="particules"
+                    ref=self.canvas.node.clone()
+                    width=Canvas::pixels(self.props.scenario.width)
+                    height=Canvas::pixels(self.props.scenario.height)
+                    onclick=self.link.callback(|e: ClickEvent| {
+                        Msg::AddAgent(Canvas::cell_at(e.offset_x(), e.offset_y()))
+                    })>
+                </canvas>
+            },
+        }
+    }
+
     fn view_row(&self, y: i32) -> Html {
         html! {
             <div class=("row")>
-                {for (0..self.props.width).map(|x| {
+                {for (0..self.props.scenario.width).map(|x| {
                     self.view_cell(Point {x, y})
                 })}
             </div>
@@ -188,67 +351,149 @@ impl Grid {
     }
 
     fn view_cell(&self, point: Point) -> Html {
-        let idx = SMA.read().unwrap().get_index(point);
+        let idx = self.sma.get_index(point);
         html! {
             <CellComponent x={point.x} y ={point.y} ref=self.refs[idx].clone() on_click=self.link.callback(Msg::AddAgent)/>
         }
     }
 
     fn view_debug(&self) -> Html {
+        // Listing every agent is costly, it is only done on demand
+        if !self.debug {
+            return html! {};
+        }
         html! {
             <div>
             {
-                SMA.write().unwrap().agents.iter().enumerate().map(|(idx, agent)| {
-                    {self.agent_info((idx, agent))}
+                self.sma.agents.iter().map(|agent| {
+                    {self.agent_info(agent)}
                 }).collect::<Html>()
             }
             </div>
         }
     }
 
-    fn agent_info(&self, (idx, agent): (usize, &AgentRef)) -> Html {
+    fn agent_info(&self, agent: &AgentRef) -> Html {
         html! {
             <div class ="row">
-                {idx}
+                {agent.id()}
                 {"| \tx : "} {agent.coordinate().x}
                 {"\ty : "} {agent.coordinate().y}
-                {"\tcollsion : "} {agent.collision()}
-                {"\t direction : "} <i class={self.dir_to_arrow(agent.direction())}></i>
+                {for agent.collision().map(|collision| html! {
+                    <span>{"\tcollsion : "} {collision}</span>
+                })}
+                {for agent.direction().map(|direction| html! {
+                    <span>{"\t direction : "} <i class={self.dir_to_arrow(direction)}></i></span>
+                })}
+                {for agent.energy().map(|energy| html! {
+                    <span>{"\t energy : "} {energy}</span>
+                })}
+                {for agent.breed_count_down().map(|breed| html! {
+                    <span>{"\t breed in : "} {breed}</span>
+                })}
             </div>
         }
     }
 
-    pub fn init_refs() -> Vec<NodeRef> {
+    /// Simulation of the scenario picked in the environment form, without any agent.
+    fn empty_sma(props: &Props) -> Sma {
+        props
+            .scenario
+            .empty()
+            .expect("the scenario is checked by the environment form")
+    }
+
+    fn is_wator(&self) -> bool {
+        self.props.scenario.model == Model::Wator
+    }
+
+    pub fn init_refs(sma: &Sma) -> Vec<NodeRef> {
         let mut refs = vec![];
-        for _ in SMA.read().unwrap().get_state() {
+        for _ in sma.get_state() {
             refs.push(NodeRef::default())
         }
 
         refs
     }
 
-    fn clear_filled_cells(&mut self) {
-        self.refs.iter().for_each(|cell_ref| {
-            if let Some(cell) = cell_ref.try_into::<Element>() {
-                cell.set_attribute("class", &format!("cell {}", Color::None.as_str()))
-                    .unwrap_or_else(|_| {
-                        trace!("Something went wrong updating html refs, please refresh the page")
-                    })
-            }
-        });
+    fn clear_agents(&self) {
+        let cells: Vec<(Point, Color)> = self
+            .sma
+            .agents
+            .iter()
+            .map(|agent| (agent.coordinate(), Color::None))
+            .collect();
+        self.paint(&cells);
+    }
+
+    fn draw_agents(&self) {
+        self.canvas.clear();
+        let points: Vec<Point> = if self.sma.env.has_resources() {
+            // Every empty cell is tinted by its resources
+            let (width, height) = (self.props.scenario.width, self.props.scenario.height);
+            (0..height).flat_map(|y| (0..width).map(move |x| Point { x, y })).collect()
+        } else {
+            self.sma.agents.iter().map(|agent| agent.coordinate()).collect()
+        };
+        self.restyle(&points);
+    }
+
+    /// Only repaint the cells touched by the last tick.
+    fn draw_changes(&self) {
+        self.restyle(self.sma.changed_cells());
+    }
+
+    fn restyle(&self, points: &[Point]) {
+        let cells: Vec<(Point, Color)> = points
+            .iter()
+            .map(|point| (*point, Color::at(&self.sma.env, *point)))
+            .collect();
+        self.paint(&cells);
     }
 
-    fn draw_agents(&mut self) {
-        SMA.read().unwrap().agents.iter().for_each(|agent| {
-            let color = Color::from(agent.collision());
+    fn paint(&self, cells: &[(Point, Color)]) {
+        match self.renderer {
+            Renderer::Cells => {
+                for (point, color) in cells {
+                    self.set_cell_color(*point, color);
+                }
+            }
+            Renderer::Canvas => self.canvas.paint(cells),
+        }
+    }
 
-            let idx = SMA.read().unwrap().get_index(agent.coordinate());
+    fn set_cell_color(&self, point: Point, color: &Color) {
+        let idx = self.sma.get_index(point);
+        if let Some(cell) = self.refs[idx].try_into::<Element>() {
+            let class = format!("cell {}", color.as_str());
+            let style = format!("background-color: {}", color.css());
+            cell.set_attribute("class", &class)
+                .and_then(|_| cell.set_attribute("style", &style))
+                .unwrap_or_else(|_| {
+                    trace!("Something went wrong updating html refs, please refresh the page")
+                })
+        }
+    }
 
-            if let Some(cell) = self.refs[idx].try_into::<Element>() {
-                cell.set_attribute("class", &format!("cell {}", color.as_str()))
-                    .expect(":(");
+    fn counters(&self) -> String {
+        let count = |kind| self.sma.agents.iter().filter(|agent| agent.kind() == kind).count();
+        match self.props.scenario.model {
+            Model::Wator => format!(
+                "turn {} | fish {} | sharks {}",
+                self.sma.turn,
+                count(AgentKind::Fish),
+                count(AgentKind::Shark)
+            ),
+            Model::Particules => {
+                format!("turn {} | particules {}", self.sma.turn, self.sma.agents.len())
             }
-        });
+        }
+    }
+
+    fn draw_counters(&self) {
+        if let Some(counters) = self.counters.try_into::<Element>() {
+            counters.set_text_content(&self.counters());
+        }
     }
 
     fn dir_to_arrow(&self, direction: Direction) -> &str {
@@ -1,2 +1,3 @@
+pub mod canvas;
 pub mod cell;
 pub mod grid;
@@ -1,13 +1,23 @@
 #![recursion_limit = "1024"]
-use yew::{html, Component, ComponentLink, Html, InputData, ShouldRender};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use particules::resource::ResourceRules;
+use particules::scenario::{Model as Simulation, Scenario};
+use particules::validation::{Problem, Validation};
+use yew::{html, ChangeData, Component, ComponentLink, Html, InputData, ShouldRender};
 mod component;
 use component::grid::Grid;
 
 pub struct Model {
     link: ComponentLink<Self>,
-    height: i32,
-    width: i32,
-    error: String,
+    scenario: Scenario,
+    /// Fields whose input could not be parsed, with the reason.
+    unparsed: BTreeMap<&'static str, String>,
+    problems: Vec<Problem>,
+    /// Resource rules as edited in the form, used once a regrowth is given.
+    resources: ResourceRules,
     redraw: bool,
 }
 
@@ -15,6 +25,61 @@ pub enum Msg {
     Update,
     UpdateHeight(String),
     UpdateWidth(String),
+    ChangeModel(Simulation),
+    UpdateSetting(Setting, String),
+    UpdateRegrowth(String),
+    UpdateCapacity(String),
+}
+
+/// Wa-Tor field edited in the environment form.
+#[derive(Clone, Copy)]
+pub enum Setting {
+    FishDensity,
+    SharkDensity,
+    FishBreedTime,
+    SharkBreedTime,
+    SharkStarveTime,
+}
+
+impl Setting {
+    const ALL: [Setting; 5] = [
+        Setting::FishDensity,
+        Setting::SharkDensity,
+        Setting::FishBreedTime,
+        Setting::SharkBreedTime,
+        Setting::SharkStarveTime,
+    ];
+
+    fn label(&self) -> &str {
+        match self {
+            Setting::FishDensity => "fish",
+            Setting::SharkDensity => "sharks",
+            Setting::FishBreedTime => "fish breed time",
+            Setting::SharkBreedTime => "shark breed time",
+            Setting::SharkStarveTime => "shark starve time",
+        }
+    }
+
+    /// Scenario field, as named in validation problems.
+    fn field(&self) -> &'static str {
+        match self {
+            Setting::FishDensity => "fish_density",
+            Setting::SharkDensity => "shark_density",
+            Setting::FishBreedTime => "fish_breed_time",
+            Setting::SharkBreedTime => "shark_breed_time",
+            Setting::SharkStarveTime => "shark_starve_time",
+        }
+    }
+
+    fn value<'a>(&self, scenario: &'a mut Scenario) -> &'a mut u8 {
+        match self {
+            Setting::FishDensity => &mut scenario.fish_density,
+            Setting::SharkDensity => &mut scenario.shark_density,
+            Setting::FishBreedTime => &mut scenario.fish_breed_time,
+            Setting::SharkBreedTime => &mut scenario.shark_breed_time,
+            Setting::SharkStarveTime => &mut scenario.shark_starve_time,
+        }
+    }
 }
 
 impl Component for Model {
@@ -23,35 +88,64 @@ impl Component for Model {
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
         Model {
             link,
-            height: 20,
-            width: 20,
+            scenario: Self::default_scenario(),
+            unparsed: BTreeMap::new(),
+            problems: vec![],
+            resources: ResourceRules::default(),
             redraw: false,
-            error: "".into(),
         }
     }
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::UpdateHeight(value) => {
-                match value.parse::<i32>() {
-                    Ok(value) => {
-                        self.error = "".into();
-                        self.height = value;
-                    }
-                    Err(e) => self.error = e.to_string(),
+                if let Some(height) = self.parse_input("height", &value) {
+                    self.scenario.height = height;
                 }
                 false
             }
             Msg::UpdateWidth(value) => {
-                match value.parse::<i32>() {
-                    Ok(value) => {
-                        self.error = "".into();
-                        self.width = value;
+                if let Some(width) = self.parse_input("width", &value) {
+                    self.scenario.width = width;
+                }
+                false
+            }
+            Msg::ChangeModel(model) => {
+                self.scenario.model = model;
+                false
+            }
+            Msg::UpdateSetting(setting, value) => {
+                if let Some(value) = self.parse_input(setting.field(), &value) {
+                    *setting.value(&mut self.scenario) = value;
+                }
+                false
+            }
+            Msg::UpdateRegrowth(value) => {
+                // Fish live without resources unless a regrowth is given
+                if value.trim().is_empty() {
+                    self.unparsed.remove("resources.regrowth");
+                    self.scenario.resources = None;
+                } else if let Some(regrowth) = self.parse_input("resources.regrowth", &value) {
+                    self.resources.regrowth = regrowth;
+                    self.scenario.resources = Some(self.resources.clone());
+                }
+                false
+            }
+            Msg::UpdateCapacity(value) => {
+                if let Some(capacity) = self.parse_input("resources.capacity", &value) {
+                    self.resources.capacity = capacity;
+                    if let Some(resources) = &mut self.scenario.resources {
+                        resources.capacity = capacity;
                     }
-                    Err(e) => self.error = e.to_string(),
                 }
                 false
             }
             Msg::Update => {
+                if self.redraw {
+                    self.problems = self.validate();
+                    if !self.problems.is_empty() {
+                        return true;
+                    }
+                }
                 self.redraw = !self.redraw;
                 true
             }
@@ -62,16 +156,27 @@ impl Component for Model {
         html! {
             <div>
             <div class="environment-form row">
-                <input height=&self.height oninput=self.link.callback(|e: InputData| Msg::UpdateWidth(e.value)) placeholder="height"> </input>
-                        <input height=&self.height oninput=self.link.callback(|e: InputData| Msg::UpdateHeight(e.value)) placeholder="width"></input>
+                <input oninput=self.link.callback(|e: InputData| Msg::UpdateWidth(e.value)) placeholder="height"> </input>
+                        <input oninput=self.link.callback(|e: InputData| Msg::UpdateHeight(e.value)) placeholder="width"></input>
+                        <select class="game-button" onchange=self.link.callback(Self::on_model_change)>
+                            <option value="particules">{"particules"}</option>
+                            <option value="wator">{"wator"}</option>
+                        </select>
+                        {for Setting::ALL.iter().map(|&setting| html! {
+                            <input oninput=self.link.callback(move |e: InputData| Msg::UpdateSetting(setting, e.value)) placeholder=setting.label()></input>
+                        })}
+                        <input oninput=self.link.callback(|e: InputData| Msg::UpdateRegrowth(e.value)) placeholder="regrowth (delay:30, logistic:0.5)"></input>
+                        <input oninput=self.link.callback(|e: InputData| Msg::UpdateCapacity(e.value)) placeholder="resource capacity"></input>
                         <button class="game-button" onclick=self.link.callback(|_| Msg::Update)>{ if self.redraw {{"Create"}} else { {"Update"} }  }</button>
-                        <p> {"height : " } {&self.height}</p>
-                        <p> {"widht : " } {self.width}</p>
+                        <p> {"height : " } {self.scenario.height}</p>
+                        <p> {"widht : " } {self.scenario.width}</p>
             </div>
-                <p color="red"> {"error : " } {&self.error}</p>
+                <ul class="problems">
+                    {for self.problems.iter().map(|problem| html! { <li>{problem.to_string()}</li> })}
+                </ul>
                 {
                     if !self.redraw {
-                       html! { <Grid height={self.height}, width = {self.width}/> }
+                       html! { <Grid scenario = {self.scenario.clone()}/> }
                     } else {
                         html! {}
                     }
@@ -80,3 +185,55 @@ impl Component for Model {
         }
     }
 }
+
+impl Model {
+    /// Small world without random particules, they are added by clicking the grid.
+    fn default_scenario() -> Scenario {
+        Scenario {
+            model: Simulation::Particules,
+            width: 20,
+            height: 20,
+            density: 0,
+            fish_density: 60,
+            shark_density: 10,
+            ..Scenario::default()
+        }
+    }
+
+    /// Remember why `value` is not a valid `field`, until the input is fixed.
+    fn parse_input<T>(&mut self, field: &'static str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match value.parse() {
+            Ok(value) => {
+                self.unparsed.remove(field);
+                Some(value)
+            }
+            Err(e) => {
+                let message = format!("invalid value '{}': {}", value, e);
+                self.unparsed.insert(field, message);
+                None
+            }
+        }
+    }
+
+    /// Every problem of the form, the unparsable inputs first.
+    fn validate(&self) -> Vec<Problem> {
+        let mut validation = Validation::new();
+        for (field, message) in self.unparsed.iter() {
+            validation.report(*field, message.as_str());
+        }
+        self.scenario.check(&mut validation);
+        validation.problems().to_vec()
+    }
+
+    fn on_model_change(data: ChangeData) -> Msg {
+        let model = match data {
+            ChangeData::Select(select) => select.value().and_then(|value| value.parse().ok()),
+            _ => None,
+        };
+        Msg::ChangeModel(model.unwrap_or(Simulation::Particules))
+    }
+}
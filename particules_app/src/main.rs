@@ -1,11 +1,30 @@
-use log::trace;
+use log::{trace, Level};
 use particules_app::Model;
+use stdweb::web::window;
 use yew::App;
 
 fn main() {
-    web_logger::init();
+    web_logger::custom_init(web_logger::Config {
+        level: log_level(),
+    });
     trace!("Initializing yew...");
     yew::initialize();
     App::<Model>::new().mount_to_body();
     yew::run_loop();
 }
+
+/// Level picked with `?log=<level>` in the page address, info if omitted.
+fn log_level() -> Level {
+    let search = window().location().and_then(|location| location.search().ok());
+    search
+        .as_ref()
+        .and_then(|search| {
+            search
+                .trim_start_matches('?')
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("log="))
+        })
+        .and_then(|level| level.parse().ok())
+        // Core events below info are emitted every tick and would flood the console
+        .unwrap_or(Level::Info)
+}
@@ -5,64 +5,142 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs;
+use std::io::ErrorKind;
 
+use log::LevelFilter;
 use nannou::prelude::*;
 
+use particules::export::{ExportFormat, Exporter};
+use particules::resource;
+use particules::scenario::Scenario;
 use particules::sma::Sma;
-use particules::SMA;
+use particules::stop::{RunOutcome, StopCondition};
+use particules::validation::Validation;
+use particules::{Error, Point};
 
 mod user_config;
 
 lazy_static! {
-    pub static ref CONFIG: user_config::Config = {
-        let mut file = File::open("config.json").unwrap();
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        serde_json::from_str(&contents).expect("expected json")
+    /// Display settings, the defaults are used without `config.json`.
+    pub static ref CONFIG: user_config::Config = match fs::read_to_string("config.json") {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+            eprintln!("Invalid configuration: {}", err);
+            std::process::exit(1);
+        }),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => user_config::Config::default(),
+        Err(err) => {
+            eprintln!("Could not read config.json: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    /// Scenario given as first argument, or else by the configuration, checked by `validate`.
+    pub static ref SCENARIO: Scenario = {
+        let path = std::env::args().nth(1).or_else(|| CONFIG.scenario.clone());
+        match path {
+            Some(path) => Scenario::read(path).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }),
+            None => Scenario::default(),
+        }
     };
 }
 
 fn main() {
+    validate();
+    init_logger();
     nannou::app(model).update(update).run();
 }
 
-struct Grid {}
+/// Exit listing every problem of the configuration and the scenario, if any.
+fn validate() {
+    let mut validation = Validation::new();
+    CONFIG.check(&mut validation);
+    SCENARIO.check(&mut validation);
+    if let Err(err) = validation.finish() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn init_logger() {
+    let level = match &CONFIG.log_level {
+        Some(level) => level.parse().unwrap_or(LevelFilter::Warn),
+        None => LevelFilter::Warn,
+    };
+    env_logger::Builder::new().filter_level(level).init();
+}
+
+struct Grid {
+    sma: Sma,
+    conditions: Vec<StopCondition>,
+}
 
 impl Grid {
-    fn new() -> Self {
-        Sma::new(CONFIG.x as i32, CONFIG.y as i32);
-        SMA.write().unwrap().gen_agents(CONFIG.density);
-        Grid {}
+    fn new() -> Result<Self, Error> {
+        let mut sma = SCENARIO.build()?;
+        sma.set_debug(CONFIG.debug.unwrap_or(false));
+        Grid::attach_exporter(&mut sma)?;
+        Ok(Grid {
+            sma,
+            conditions: Grid::stop_conditions(),
+        })
+    }
+
+    fn attach_exporter(sma: &mut Sma) -> Result<(), Error> {
+        if let Some(path) = &CONFIG.export {
+            let format = match &CONFIG.export_format {
+                Some(name) => name.parse()?,
+                None => ExportFormat::default(),
+            };
+            sma.attach_exporter(Exporter::create(path, format)?);
+        }
+        Ok(())
     }
 
-    fn new_fish() -> Self {
-        Sma::new_with_fish(
-            CONFIG.x as i32,
-            CONFIG.y as i32,
-            CONFIG.fish_breed_time,
-            CONFIG.shark_breed_time,
-            CONFIG.shark_starve_time,
-        );
-        SMA.write()
-            .unwrap()
-            .gen_fish_agents(CONFIG.fish_density, CONFIG.shark_density);
-        Grid {}
+    /// Extinction and a full grid always end the run, the other conditions are opt-in.
+    fn stop_conditions() -> Vec<StopCondition> {
+        let mut conditions = vec![StopCondition::Extinction, StopCondition::GridFull];
+        if let Some(turns) = CONFIG.max_turns {
+            conditions.push(StopCondition::MaxTurns(turns));
+        }
+        if let Some(ticks) = CONFIG.steady_ticks {
+            conditions.push(StopCondition::SteadyState(ticks));
+        }
+        if let Some(count) = CONFIG.cycles {
+            conditions.push(StopCondition::PopulationCycle(count));
+        }
+        conditions
     }
 
     // This is the easy part, just draw the cells fill white if 1, black if 0
     fn display(&self, draw: &app::Draw) {
         let offset = CONFIG.cell_size;
 
-        let width = CONFIG.x * offset;
-        let height = CONFIG.y * offset;
+        let width = SCENARIO.width as f32 * offset;
+        let height = SCENARIO.height as f32 * offset;
         draw.rect()
             .w_h(width, height)
             .rgb(1.0, 1.0, 1.0)
             .stroke(rgb(0.0, 0.0, 0.0));
 
-        SMA.write().unwrap().agents.iter().for_each(|agent| {
+        // Resources show under the agents, cells eaten out stay white
+        for y in 0..SCENARIO.height {
+            for x in 0..SCENARIO.width {
+                match self.sma.env.resource(Point::new(x, y)) {
+                    Some(fraction) if fraction > 0.0 => {
+                        let x = (x as f32 * CONFIG.cell_size) - width / 2.0 + offset / 2.0;
+                        let y = (y as f32 * CONFIG.cell_size) - height / 2.0 + offset / 2.0;
+                        self.display_agent(&draw, resource::tint(fraction), x, y);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.sma.agents.iter().for_each(|agent| {
             let x = agent.coordinate().x as f32;
             let y = agent.coordinate().y as f32;
             let x = (x * CONFIG.cell_size) - width / 2.0 + offset / 2.0;
@@ -82,6 +160,7 @@ impl Grid {
 struct Model {
     pub grid: Grid,
     pub pause: bool,
+    pub outcome: Option<RunOutcome>,
 }
 
 fn model(app: &App) -> Model {
@@ -92,19 +171,28 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
 
-    if CONFIG.fish {
-        let grid = Grid::new_fish();
-        Model { grid, pause: true }
-    } else {
-        let grid = Grid::new();
-        Model { grid, pause: true }
+    match Grid::new() {
+        Ok(grid) => Model {
+            grid,
+            pause: true,
+            outcome: None,
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     }
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    if !model.pause {
-        SMA.write().unwrap().tick();
+    if !model.pause && model.outcome.is_none() {
+        model.grid.sma.tick();
         model.pause = true;
+
+        model.outcome = model.grid.sma.stop_reason(&model.grid.conditions);
+        if let Some(outcome) = &model.outcome {
+            println!("stopped after {} ticks: {}", model.grid.sma.turn, outcome);
+        }
     }
 }
 
@@ -114,6 +202,13 @@ fn view(app: &App, m: &Model, frame: &Frame) {
     draw.background().color(rgb(1.0, 1.0, 1.0));
 
     m.grid.display(&draw);
+    if let Some(outcome) = &m.outcome {
+        let bottom = -(SCENARIO.height as f32) * CONFIG.cell_size / 2.0;
+        draw.text(&format!("stopped after {} ticks: {}", m.grid.sma.turn, outcome))
+            .x_y(0.0, bottom - 20.0)
+            .w(SCENARIO.width as f32 * CONFIG.cell_size)
+            .color(BLACK);
+    }
 
     // Write the result of our drawing to the window's frame.
     draw.to_frame(app, &frame).unwrap();
@@ -1,15 +1,63 @@
+use log::LevelFilter;
+
+use particules::export::ExportFormat;
+use particules::validation::Validation;
+
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
-    pub x: f32,
-    pub y: f32,
     pub cell_size: f32,
-    pub agents: u32,
-    pub density: u8,
-    pub grid: bool,
-    pub fish: bool,
-    pub fish_density: u8,
-    pub shark_density: u8,
-    pub fish_breed_time: u8,
-    pub shark_breed_time: u8,
-    pub shark_starve_time: u8,
+    /// Scenario file (json, toml or ron), the first command line argument takes precedence.
+    pub scenario: Option<String>,
+    pub log_level: Option<String>,
+    pub debug: Option<bool>,
+    pub export: Option<String>,
+    pub export_format: Option<String>,
+    pub max_turns: Option<i32>,
+    pub steady_ticks: Option<usize>,
+    pub cycles: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cell_size: 16.0,
+            scenario: None,
+            log_level: None,
+            debug: None,
+            export: None,
+            export_format: None,
+            max_turns: None,
+            steady_ticks: None,
+            cycles: None,
+        }
+    }
+}
+
+impl Config {
+    /// Report every invalid field, alongside the problems of the scenario.
+    pub fn check(&self, validation: &mut Validation) {
+        validation.check_range("cell_size", self.cell_size, 1.0, 256.0);
+        if let Some(level) = &self.log_level {
+            if level.parse::<LevelFilter>().is_err() {
+                let message =
+                    format!("must be off, error, warn, info, debug or trace, got '{}'", level);
+                validation.report("log_level", message);
+            }
+        }
+        if let Some(format) = &self.export_format {
+            if let Err(err) = format.parse::<ExportFormat>() {
+                validation.report("export_format", err.to_string());
+            }
+        }
+        if let Some(turns) = self.max_turns {
+            validation.check_min("max_turns", turns, 0);
+        }
+        if let Some(ticks) = self.steady_ticks {
+            validation.check_min("steady_ticks", ticks, 1);
+        }
+        if let Some(count) = self.cycles {
+            validation.check_min("cycles", count, 1);
+        }
+    }
 }